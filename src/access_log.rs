@@ -0,0 +1,103 @@
+use std::{net::IpAddr, sync::Arc};
+
+use axum::{body::Body, extract::Request, http::header, response::Response};
+use chrono::{DateTime, Local};
+
+use crate::{counting::CountingStream, transfer::Transfer};
+
+// tracing 的 target, main 里把这个 target 单独写到 access.log
+pub const TARGET: &str = "access";
+
+/// 下载的响应里带上分享的名字, 记到访问日志里
+#[derive(Debug, Clone)]
+pub struct ShareName(pub String);
+
+/// 一次请求, body 发完 (或者连接断开) 时才知道发了多少字节
+#[derive(Debug, Clone)]
+pub struct AccessEntry {
+    pub time: DateTime<Local>,
+    pub client: IpAddr,
+    pub user_agent: String,
+    pub method: String,
+    pub path: String,
+    pub share: Option<String>,
+    pub status: u16,
+    pub bytes: u64,
+}
+
+impl AccessEntry {
+    pub fn new(client: IpAddr, request: &Request) -> Self {
+        Self {
+            time: Local::now(),
+            client,
+            user_agent: request
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string(),
+            method: request.method().to_string(),
+            path: request.uri().path().to_string(),
+            share: None,
+            status: 0,
+            bytes: 0,
+        }
+    }
+
+    fn set_response(&mut self, response: &Response) {
+        self.share = response
+            .extensions()
+            .get::<ShareName>()
+            .map(|name| name.0.clone());
+        self.status = response.status().as_u16();
+    }
+
+    /// 写一行 JSON 到 access.log
+    fn write(&self) {
+        tracing::info!(
+            target: TARGET,
+            client = %self.client,
+            user_agent = self.user_agent,
+            method = self.method,
+            path = self.path,
+            share = self.share,
+            status = self.status,
+            bytes = self.bytes,
+        );
+    }
+}
+
+/// 给响应的 body 计数, 结束时写日志并交给 on_finish (通知 TUI).
+/// 下载的响应带着 Transfer 时同一个 body 顺便更新进度, 不再包一层
+pub fn log_body(
+    response: Response,
+    mut entry: AccessEntry,
+    on_finish: impl Fn(AccessEntry) + Send + Sync + 'static,
+) -> Response {
+    entry.set_response(&response);
+    let transfer = response.extensions().get::<Arc<Transfer>>().cloned();
+    let cancel = transfer.as_ref().map(|t| t.cancel_token());
+    response.map(|body| {
+        let transfer_clone = transfer.clone();
+        let stream = CountingStream::new(
+            body.into_data_stream(),
+            move |len| {
+                if let Some(transfer) = &transfer_clone {
+                    transfer.add_sent(len);
+                }
+            },
+            move |bytes| {
+                if let Some(transfer) = &transfer {
+                    transfer.finish();
+                }
+                entry.bytes = bytes;
+                entry.write();
+                on_finish(entry);
+            },
+        );
+        match cancel {
+            Some(cancel) => Body::from_stream(stream.with_cancel(cancel)),
+            None => Body::from_stream(stream),
+        }
+    })
+}
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use async_compression::tokio::write::GzipEncoder;
+use async_zip::{
+    base::write::ZipFileWriter, error::ZipError, Compression, ZipDateTime, ZipEntryBuilder,
+};
+use axum::body::Body;
+use chrono::{DateTime, Utc};
+use tokio::{
+    fs::File,
+    io::{duplex, AsyncWrite, AsyncWriteExt, DuplexStream},
+};
+use tokio_util::{compat::TokioAsyncReadCompatExt, io::ReaderStream};
+use walkdir::{DirEntry, WalkDir};
+
+// 打包和发送之间的管道缓冲大小
+const PIPE_SIZE: usize = 64 * 1024;
+
+/// 边打包边发送 dir, 不落盘
+pub fn zip_dir(dir: PathBuf) -> Body {
+    let (writer, reader) = duplex(PIPE_SIZE);
+
+    tokio::spawn(async move {
+        if let Err(e) = write_zip(vec![dir.clone()], writer).await {
+            // 客户端中途断开也会走到这里
+            tracing::error!("zip {:?} fail, e: {}", dir, e);
+        }
+    });
+
+    Body::from_stream(ReaderStream::new(reader))
+}
+
+/// 边打包边发送 roots, 每个 root 可以是文件或目录, gzip 为 true 时输出 tar.gz
+pub fn tar_paths(roots: Vec<PathBuf>, gzip: bool) -> Body {
+    let (writer, reader) = duplex(PIPE_SIZE);
+
+    tokio::spawn(async move {
+        let result = if gzip {
+            write_tar(roots, GzipEncoder::new(writer)).await
+        } else {
+            write_tar(roots, writer).await
+        };
+        if let Err(e) = result {
+            tracing::error!("tar fail, e: {}", e);
+        }
+    });
+
+    Body::from_stream(ReaderStream::new(reader))
+}
+
+/// 遍历 roots, 返回 (包内路径, entry), 包内路径以 root 自身的名字开头
+async fn collect_entries(roots: Vec<PathBuf>) -> Vec<(String, DirEntry)> {
+    tokio::task::spawn_blocking(move || {
+        roots
+            .iter()
+            .flat_map(|root| {
+                let base = root.parent().unwrap_or(root).to_path_buf();
+                WalkDir::new(root)
+                    .sort_by_file_name()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |entry| {
+                        let name = entry
+                            .path()
+                            .strip_prefix(&base)
+                            .ok()?
+                            .components()
+                            .map(|c| c.as_os_str().to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("/");
+                        Some((name, entry))
+                    })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default()
+}
+
+async fn write_zip(roots: Vec<PathBuf>, writer: DuplexStream) -> Result<(), ZipError> {
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    for (mut name, entry) in collect_entries(roots).await {
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            name.push('/');
+            let builder =
+                ZipEntryBuilder::new(name.into(), Compression::Stored).unix_permissions(0o40755);
+            let builder = with_modified(builder, &entry);
+            zip.write_entry_whole(builder, &[]).await?;
+        } else if file_type.is_file() {
+            let file = match File::open(entry.path()).await {
+                Ok(file) => file,
+                Err(e) => {
+                    tracing::warn!("zip skip {:?}, e: {}", entry.path(), e);
+                    continue;
+                }
+            };
+            let builder =
+                ZipEntryBuilder::new(name.into(), Compression::Deflate).unix_permissions(0o100644);
+            let builder = with_modified(builder, &entry);
+
+            let mut entry_writer = zip.write_entry_stream(builder).await?;
+            futures::io::copy(&mut file.compat(), &mut entry_writer).await?;
+            entry_writer.close().await?;
+        }
+        // 软链接等其它类型不打包, 避免带出分享目录之外的文件
+    }
+    zip.close().await?;
+
+    Ok(())
+}
+
+fn with_modified(builder: ZipEntryBuilder, entry: &DirEntry) -> ZipEntryBuilder {
+    match entry.metadata().ok().and_then(|m| m.modified().ok()) {
+        Some(modified) => {
+            let modified: DateTime<Utc> = modified.into();
+            builder.last_modification_date(ZipDateTime::from_chrono(&modified))
+        }
+        None => builder,
+    }
+}
+
+async fn write_tar<W>(roots: Vec<PathBuf>, writer: W) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin + Send + Sync + 'static,
+{
+    let mut tar = tokio_tar::Builder::new(writer);
+    for (name, entry) in collect_entries(roots).await {
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            tar.append_dir(&name, entry.path()).await?;
+        } else if file_type.is_file() {
+            let mut file = match File::open(entry.path()).await {
+                Ok(file) => file,
+                Err(e) => {
+                    tracing::warn!("tar skip {:?}, e: {}", entry.path(), e);
+                    continue;
+                }
+            };
+            tar.append_file(&name, &mut file).await?;
+        }
+        // 和 zip 一样, 不打包软链接
+    }
+    // gzip 需要 shutdown 才会写入尾部
+    tar.into_inner().await?.shutdown().await
+}
@@ -0,0 +1,49 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+// 编译进程序, 局域网里没有外网也能正常显示
+const ASSET_ARR: &[(&str, &str, &[u8])] = &[
+    (
+        "app.css",
+        "text/css; charset=utf-8",
+        include_bytes!("../static/app.css"),
+    ),
+    (
+        "app.js",
+        "text/javascript; charset=utf-8",
+        include_bytes!("../static/app.js"),
+    ),
+    // htmx 1.9.3 官方的 dist/htmx.min.js
+    (
+        "htmx.min.js",
+        "text/javascript; charset=utf-8",
+        include_bytes!("../static/htmx.min.js"),
+    ),
+    // 只实现了首页用到的 ws-connect
+    (
+        "ws.js",
+        "text/javascript; charset=utf-8",
+        include_bytes!("../static/ws.js"),
+    ),
+];
+
+// 内容随程序版本变化, 升级后浏览器最多一天就会重新取
+const CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// `/static/:file`, 不需要 PIN, 登录页也要用
+pub async fn static_file(Path(file): Path<String>) -> Response {
+    match ASSET_ARR.iter().find(|(name, _, _)| *name == file) {
+        Some((_, content_type, content)) => (
+            [
+                (header::CONTENT_TYPE, *content_type),
+                (header::CACHE_CONTROL, CACHE_CONTROL),
+            ],
+            *content,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
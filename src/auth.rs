@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::http::{header, HeaderMap};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::Rng;
+
+use crate::utils::random_string;
+
+pub const COOKIE_NAME: &str = "kk_session";
+// curl 可以直接带上 PIN: `curl -H "X-KK-PIN: 123456"`
+pub const PIN_HEADER: &str = "x-kk-pin";
+
+// session token 的长度
+const TOKEN_LEN: usize = 32;
+// 连续输错这么多次后开始锁定, 之后每次锁定时间加倍
+const MAX_ATTEMPTS: u32 = 5;
+const LOCK_TIME: Duration = Duration::from_secs(30);
+const MAX_LOCK_TIME: Duration = Duration::from_secs(3600);
+
+/// 请求里带的凭据是否通过, 带了 PIN 的不管对错都算一次尝试
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// cookie 里的 token 有效
+    Session,
+    /// 没有带 PIN, 或者 cookie 已经失效
+    Missing,
+    RightPin,
+    WrongPin,
+}
+
+/// 访问网页需要的 PIN, 浏览器登录一次后用 cookie 里的 token
+#[derive(Debug, Clone)]
+pub struct Auth {
+    pub pin: String,
+    // 每次换 PIN 都重新生成, 旧的 cookie 随之失效
+    token: String,
+}
+
+impl Auth {
+    pub fn new(pin: String) -> Self {
+        Self {
+            pin,
+            token: random_string(TOKEN_LEN),
+        }
+    }
+
+    /// 随机 6 位数字 PIN
+    pub fn random() -> Self {
+        Self::new(format!("{:06}", rand::thread_rng().gen_range(0..1_000_000)))
+    }
+
+    /// cookie, PIN header, basic auth 任一通过即可
+    pub fn check(&self, headers: &HeaderMap) -> Check {
+        if self.check_cookie(headers) {
+            return Check::Session;
+        }
+        let pin_arr = [pin_header(headers), basic_pin(headers)];
+        let mut pin_arr = pin_arr.iter().flatten().peekable();
+        if pin_arr.peek().is_none() {
+            Check::Missing
+        } else if pin_arr.any(|pin| self.is_pin(pin)) {
+            Check::RightPin
+        } else {
+            Check::WrongPin
+        }
+    }
+
+    pub fn is_pin(&self, pin: &str) -> bool {
+        eq_const(pin.trim().as_bytes(), self.pin.as_bytes())
+    }
+
+    /// HTTPS 时加上 Secure, 不会在明文连接里发出去
+    pub fn set_cookie(&self, is_secure: bool) -> String {
+        let secure = if is_secure { "; Secure" } else { "" };
+        format!(
+            "{COOKIE_NAME}={}; Path=/; HttpOnly; SameSite=Lax{secure}",
+            self.token
+        )
+    }
+
+    fn check_cookie(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|kv| kv.trim().split_once('='))
+            .any(|(k, v)| k == COOKIE_NAME && eq_const(v.as_bytes(), self.token.as_bytes()))
+    }
+}
+
+fn pin_header(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(PIN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// `curl -u any:123456`, 用户名随意, 密码是 PIN
+fn basic_pin(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .and_then(|v| STANDARD.decode(v.trim()).ok())
+        .and_then(|v| String::from_utf8(v).ok())
+        .and_then(|v| v.split_once(':').map(|(_, pin)| pin.to_string()))
+}
+
+/// 比较用时和内容无关, 不能靠响应时间一位一位猜
+fn eq_const(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 按 ip 限制猜 PIN 的次数, 登录表单, PIN header 和 basic auth 共用
+#[derive(Debug, Default)]
+pub struct Throttle {
+    attempt_map: Mutex<HashMap<IpAddr, Attempts>>,
+}
+
+#[derive(Debug)]
+struct Attempts {
+    count: u32,
+    locked_until: Instant,
+}
+
+impl Throttle {
+    /// 验证 PIN 之前调用, 先记上这一次, 同时发来的请求也不能多猜;
+    /// 锁定中返回还要等多久
+    pub fn attempt(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut attempt_map = self.attempt_map.lock().unwrap();
+        // 锁定早已结束的 ip 不再记着
+        attempt_map.retain(|_, a| now < a.locked_until + MAX_LOCK_TIME);
+        let attempts = attempt_map.entry(throttle_key(ip)).or_insert(Attempts {
+            count: 0,
+            locked_until: now,
+        });
+        if now < attempts.locked_until {
+            return Err(attempts.locked_until - now);
+        }
+        attempts.count += 1;
+        if attempts.count >= MAX_ATTEMPTS {
+            let times = (attempts.count - MAX_ATTEMPTS).min(16);
+            attempts.locked_until = now + (LOCK_TIME * 2u32.pow(times)).min(MAX_LOCK_TIME);
+        }
+        Ok(())
+    }
+
+    /// PIN 正确, 清掉这个 ip 的记录
+    pub fn reset(&self, ip: IpAddr) {
+        self.attempt_map.lock().unwrap().remove(&throttle_key(ip));
+    }
+}
+
+/// ipv6 的客户端通常有整个 /64, 换个地址不能重新开始猜, 按 /64 计
+fn throttle_key(ip: IpAddr) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V6(ip) => IpAddr::V6((u128::from(ip) & !0u128 << 64).into()),
+        ip => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn check_pin() {
+        let auth = Auth::new("123456".to_string());
+        assert_eq!(auth.check(&HeaderMap::new()), Check::Missing);
+        let pin_header = header::HeaderName::from_static(PIN_HEADER);
+        assert_eq!(
+            auth.check(&headers(pin_header.clone(), " 123456 ")),
+            Check::RightPin
+        );
+        assert_eq!(auth.check(&headers(pin_header, "12345")), Check::WrongPin);
+        // any:123456
+        let basic = headers(header::AUTHORIZATION, "Basic YW55OjEyMzQ1Ng==");
+        assert_eq!(auth.check(&basic), Check::RightPin);
+        let cookie = headers(header::COOKIE, &format!("a=b; {COOKIE_NAME}=old"));
+        assert_eq!(auth.check(&cookie), Check::Missing);
+        let cookie = headers(header::COOKIE, &format!("{COOKIE_NAME}={}", auth.token));
+        assert_eq!(auth.check(&cookie), Check::Session);
+    }
+
+    #[test]
+    fn throttle_locks_after_max_attempts() {
+        let throttle = Throttle::default();
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5));
+        for _ in 0..MAX_ATTEMPTS {
+            assert!(throttle.attempt(ip).is_ok());
+        }
+        let wait = throttle.attempt(ip).unwrap_err();
+        assert!(wait > LOCK_TIME - Duration::from_secs(1) && wait <= LOCK_TIME);
+        // 别的 ip 不受影响
+        assert!(throttle.attempt(IpAddr::V4(Ipv4Addr::LOCALHOST)).is_ok());
+
+        throttle.reset(ip);
+        assert!(throttle.attempt(ip).is_ok());
+    }
+
+    #[test]
+    fn throttle_ipv6_by_prefix() {
+        let throttle = Throttle::default();
+        for i in 0..MAX_ATTEMPTS {
+            let ip = format!("2001:db8:1:2::{i:x}").parse().unwrap();
+            assert!(throttle.attempt(ip).is_ok());
+        }
+        // 同一个 /64 里换地址也被锁定
+        assert!(throttle
+            .attempt("2001:db8:1:2::ffff".parse().unwrap())
+            .is_err());
+        assert!(throttle.attempt("2001:db8:1:3::1".parse().unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,69 @@
+use std::{net::IpAddr, path::PathBuf};
+
+use clap::Parser;
+
+use crate::ip_filter::Cidr;
+
+/// kk is a command line file share manager
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Config file [default: <config dir>/kk/config.toml]
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Port of the web page [default: 33231]
+    #[arg(short, long)]
+    pub port: Option<u16>,
+
+    /// Address to listen on [default: 0.0.0.0]
+    #[arg(short, long)]
+    pub bind: Option<IpAddr>,
+
+    /// Only these ips or subnets can visit, like `192.168.1.0/24`, can repeat
+    #[arg(long, value_name = "CIDR")]
+    pub allow: Vec<Cidr>,
+
+    /// These ips or subnets can not visit, checked before --allow, can repeat
+    #[arg(long, value_name = "CIDR")]
+    pub deny: Vec<Cidr>,
+
+    /// Serve HTTPS, with --tls-cert/--tls-key or the certificate in the config file, otherwise a self-signed one
+    #[arg(long)]
+    pub tls: bool,
+
+    /// PEM certificate (chain) for HTTPS, implies --tls
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for HTTPS, implies --tls
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Start directory of the file browser, default is the current directory
+    #[arg(short, long)]
+    pub dir: Option<PathBuf>,
+
+    /// Directory of the log files [default: <state dir>/kk/log]
+    #[arg(long)]
+    pub log_dir: Option<PathBuf>,
+
+    /// Log level or filter like `debug` or `kk=debug,tower_http=info`, overrides RUST_LOG
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Do not write any log file
+    #[arg(long)]
+    pub no_log_file: bool,
+
+    /// Run without the terminal UI, serve the shares until SIGINT/SIGTERM
+    #[arg(long)]
+    pub headless: bool,
+
+    /// With --headless, also serve and update the shares saved by the terminal UI
+    #[arg(long, requires = "headless")]
+    pub restore: bool,
+
+    /// Files or directories to share at startup
+    pub paths: Vec<PathBuf>,
+}
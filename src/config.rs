@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    consts::{COLOR_BG, COLOR_FG, COLOR_HIGHLIGHT},
+    ip_filter::Cidr,
+    keymap::{Action, KeySeq},
+};
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// `<config dir>/kk/config.toml`, 所有字段都可以省略, 命令行参数优先
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: Option<u16>,
+    pub bind: Option<IpAddr>,
+    /// 只允许这些 ip 或网段访问, 为空不限制
+    pub allow: Vec<Cidr>,
+    /// 拒绝这些 ip 或网段, 优先于 allow
+    pub deny: Vec<Cidr>,
+    pub dir: Option<PathBuf>,
+    /// 启动时就分享的文件或目录, 和命令行参数里的合并
+    pub shares: Vec<PathBuf>,
+    pub log_dir: Option<PathBuf>,
+    /// 同 RUST_LOG, 如 `debug`
+    pub log_level: Option<String>,
+    /// false 时不写日志文件
+    pub log_file: Option<bool>,
+    pub colors: Colors,
+    pub auth: AuthConfig,
+    pub tls: TlsConfig,
+    /// 动作到按键列表, 如 `move_down = ["j", "down"]`
+    pub keys: HashMap<Action, Vec<KeySeq>>,
+}
+
+/// 颜色可以是名字 (`green`, `darkgray`), 序号 (`10`) 或者 `#rrggbb`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    #[serde(deserialize_with = "de_color")]
+    pub fg: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub bg: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub highlight: Color,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            fg: COLOR_FG,
+            bg: COLOR_BG,
+            highlight: COLOR_HIGHLIGHT,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// 启动时就开启 PIN
+    pub enabled: bool,
+    /// 固定的 PIN, 不设置每次开启都随机生成
+    pub pin: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// 证书和私钥都设置才用, 否则用自签名证书
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+impl Config {
+    /// 指定的文件必须存在, 默认位置的文件不存在就用默认配置
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        let (path, is_default) = match path {
+            Some(path) => (path.to_path_buf(), false),
+            None => match dirs::config_dir() {
+                Some(dir) => (dir.join("kk").join(CONFIG_FILE), true),
+                None => return Ok(Self::default()),
+            },
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if is_default && e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {e}", path.display()))),
+        };
+        toml::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        })
+    }
+}
+
+fn de_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Color::from_str(&s).map_err(|_| serde::de::Error::custom(format!("invalid color `{s}`")))
+}
@@ -1,102 +1,75 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io,
-    net::IpAddr,
     path::{Path, PathBuf},
+    sync::mpsc::{Receiver, Sender},
     sync::Arc,
-    thread::sleep,
     time::Duration,
 };
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
-use local_ip_address::local_ip;
+use crossterm::event::{Event, KeyEventKind};
+use qrcode::{render::unicode::Dense1x2, QrCode};
 use ratatui::{prelude::*, widgets::*};
-use tokio::sync::{mpsc::Sender, RwLock};
-
-use crate::{consts::*, utils::sort_files};
+use tokio::sync::{mpsc, RwLock};
+
+use crate::{
+    access_log::AccessEntry,
+    auth::Auth,
+    config::{Colors, Config},
+    event::{self, AppEvent, DirWatcher},
+    keymap::{Action, Keymap},
+    receive::ReceiveInfo,
+    share::Share,
+    transfer::{self, Transfer, TransferState},
+    utils::{cmp_path, format_size, sort_files},
+};
 
-pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
-    let local_ip_addr = local_ip().unwrap();
+// Log 里最多保留的访问记录
+const LOG_LEN: usize = 500;
 
-    loop {
-        sleep(Duration::from_millis(50));
-        terminal.draw(|f| ui(f, &mut app, local_ip_addr))?;
+// 没有其它事件时也定时重绘, 分享的剩余时间要更新
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
 
-        let mut is_left_ctrl = false;
+pub fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+    tx: Sender<AppEvent>,
+    rx: Receiver<AppEvent>,
+) -> io::Result<()> {
+    event::spawn_input(tx.clone());
+    event::spawn_tick(tx.clone(), TICK_INTERVAL);
+    let mut dir_watcher = DirWatcher::new(tx);
 
-        if let Event::Key(key) = event::read()? {
-            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                is_left_ctrl = true;
-            }
+    loop {
+        if let Some(current) = &app.dir_info.current {
+            dir_watcher.watch(&current.path);
+        }
+        terminal.draw(|f| ui(f, &mut app))?;
 
-            if key.kind == KeyEventKind::Press {
-                // match key_code
-                match key.code {
-                    KeyCode::Char('Q') => return Ok(()),
-                    KeyCode::Char('h') => {
-                        // 按下ctrl, 切换dir, 否则在当前block切换
-                        if is_left_ctrl {
-                            match app.get_current_block() {
-                                CurrentBlock::Dir => app.set_current_block(CurrentBlock::Shares),
-                                CurrentBlock::Shares => app.set_current_block(CurrentBlock::Dir),
-                            }
-                        } else if app.current_block == CurrentBlock::Dir {
-                            app.dir_info.set_current_to_parent()?;
-                        }
-                    }
-                    KeyCode::Char('l') => {
-                        // 按下ctrl, 切换dir, 否则在当前block切换
-                        if is_left_ctrl {
-                            match app.get_current_block() {
-                                CurrentBlock::Dir => app.set_current_block(CurrentBlock::Shares),
-                                CurrentBlock::Shares => app.set_current_block(CurrentBlock::Dir),
-                            }
-                        } else if app.current_block == CurrentBlock::Dir {
-                            app.dir_info.set_current_to_child()?;
-                        }
-                    }
-                    KeyCode::Char('j') => match app.current_block {
-                        CurrentBlock::Dir => {
-                            app.dir_info.set_current_list_state_next()?;
-                        }
-                        CurrentBlock::Shares => {
-                            app.share_info.next();
-                        }
-                    },
-                    KeyCode::Char('k') => match app.current_block {
-                        CurrentBlock::Dir => {
-                            app.dir_info.set_current_list_state_prev()?;
-                        }
-                        CurrentBlock::Shares => {
-                            app.share_info.prev();
-                        }
-                    },
-                    KeyCode::Char('=') => match app.current_block {
-                        CurrentBlock::Dir => {
-                            if let Some(file) = app.get_current_select_file() {
-                                if file.is_file() {
-                                    app.share_info.add(file);
-                                    let _ = app.tx.blocking_send(());
-                                }
-                            }
-                        }
-                        CurrentBlock::Shares => {}
-                    },
-                    KeyCode::Char('-') => match app.current_block {
-                        CurrentBlock::Dir => {}
-                        CurrentBlock::Shares => {
-                            app.share_info.remove();
-                            let _ = app.tx.blocking_send(());
-                        }
-                    },
-                    KeyCode::Char('C') => {
-                        app.share_info.clear();
-                        let _ = app.tx.blocking_send(());
+        // 等到一个事件, 再把积压的一起处理完, 只重绘一次
+        let Ok(first) = rx.recv() else {
+            return Ok(());
+        };
+        let mut is_dir_changed = false;
+        for app_event in std::iter::once(first).chain(rx.try_iter()) {
+            match app_event {
+                AppEvent::Input(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    match app.keymap.resolve(key) {
+                        Some(Action::Quit) => return Ok(()),
+                        Some(action) => app.handle_action(action)?,
+                        None => {}
                     }
-                    _ => {}
                 }
+                AppEvent::DirChanged => is_dir_changed = true,
+                AppEvent::Transfer(transfer) => app.transfer_info.add(transfer),
+                AppEvent::Access(entry) => app.log_info.add(entry),
+                AppEvent::Input(_) | AppEvent::Changed | AppEvent::Tick => {}
             }
         }
+        if is_dir_changed {
+            app.dir_info.refresh()?;
+        }
+        app.transfer_info.retain_recent();
     }
 }
 
@@ -105,31 +78,172 @@ enum CurrentBlock {
     #[default]
     Dir,
     Shares,
+    Transfers,
+    Log,
+}
+
+/// 网页的访问地址, 如 `https://192.168.1.5:33231`, HTTPS 时带上证书指纹
+pub struct Visit {
+    pub url: String,
+    pub fingerprint: Option<String>,
 }
 
 pub struct App {
     current_block: CurrentBlock,
     dir_info: DirInfo,
     share_info: ShareInfo,
+    receive_info: Arc<RwLock<ReceiveInfo>>,
+    auth: Arc<RwLock<Option<Auth>>>,
+    transfer_info: TransferInfo,
+    log_info: LogInfo,
+    visit: Visit,
+    colors: Colors,
+    // 配置文件里固定的 PIN
+    pin: Option<String>,
+    keymap: Keymap,
+    // 列表能显示的行数, 翻页用, 每次绘制时更新
+    page_height: usize,
+    // 显示二维码的 url, None 时不显示
+    qr_url: Option<String>,
     // 发送share info change
-    tx: Sender<()>,
+    tx: mpsc::Sender<()>,
 }
 
 impl App {
     pub fn new(
-        tx: Sender<()>,
+        tx: mpsc::Sender<()>,
         current_dir: PathBuf,
-        path_arr: Arc<RwLock<Vec<PathBuf>>>,
+        share_arr: Arc<RwLock<Vec<Share>>>,
+        receive_info: Arc<RwLock<ReceiveInfo>>,
+        auth: Arc<RwLock<Option<Auth>>>,
+        visit: Visit,
+        config: &Config,
     ) -> io::Result<Self> {
         let s = Self {
             current_block: CurrentBlock::Dir,
             dir_info: DirInfo::new(current_dir)?,
-            share_info: ShareInfo::new(path_arr),
+            share_info: ShareInfo::new(share_arr),
+            receive_info,
+            auth,
+            transfer_info: TransferInfo::new(),
+            log_info: LogInfo::new(),
+            visit,
+            colors: config.colors,
+            pin: config.auth.pin.clone(),
+            keymap: Keymap::new(&config.keys),
+            page_height: 0,
+            qr_url: None,
             tx,
         };
         Ok(s)
     }
 
+    fn handle_action(&mut self, action: Action) -> io::Result<()> {
+        let half_page = (self.page_height / 2).max(1);
+        let page = self.page_height.max(1);
+        match action {
+            Action::Quit => {}
+            Action::SwitchPanel => match self.get_current_block() {
+                CurrentBlock::Dir => self.set_current_block(CurrentBlock::Shares),
+                CurrentBlock::Shares => self.set_current_block(CurrentBlock::Transfers),
+                CurrentBlock::Transfers => self.set_current_block(CurrentBlock::Log),
+                CurrentBlock::Log => self.set_current_block(CurrentBlock::Dir),
+            },
+            Action::MoveUp => match self.current_block {
+                CurrentBlock::Dir => self.dir_info.set_current_list_state_prev()?,
+                CurrentBlock::Shares => self.share_info.prev(),
+                CurrentBlock::Transfers => self.transfer_info.prev(),
+                CurrentBlock::Log => self.log_info.jump(|idx, _| idx.saturating_sub(1)),
+            },
+            Action::MoveDown => match self.current_block {
+                CurrentBlock::Dir => self.dir_info.set_current_list_state_next()?,
+                CurrentBlock::Shares => self.share_info.next(),
+                CurrentBlock::Transfers => self.transfer_info.next(),
+                CurrentBlock::Log => self.log_info.jump(|idx, len| (idx + 1).min(len - 1)),
+            },
+            Action::MoveTop => self.jump(|_, _| 0)?,
+            Action::MoveBottom => self.jump(|_, len| len - 1)?,
+            Action::HalfPageUp => self.jump(|idx, _| idx.saturating_sub(half_page))?,
+            Action::HalfPageDown => self.jump(|idx, len| (idx + half_page).min(len - 1))?,
+            Action::PageUp => self.jump(|idx, _| idx.saturating_sub(page))?,
+            Action::PageDown => self.jump(|idx, len| (idx + page).min(len - 1))?,
+            Action::ParentDir => {
+                if self.current_block == CurrentBlock::Dir {
+                    self.dir_info.set_current_to_parent()?;
+                }
+            }
+            Action::EnterDir => {
+                if self.current_block == CurrentBlock::Dir {
+                    self.dir_info.set_current_to_child()?;
+                }
+            }
+            Action::AddShare => {
+                if self.current_block == CurrentBlock::Dir {
+                    if let Some(file) = self.get_current_select_file() {
+                        self.share_info.add(file);
+                        let _ = self.tx.blocking_send(());
+                    }
+                }
+            }
+            Action::RemoveShare => {
+                if self.current_block == CurrentBlock::Shares {
+                    self.share_info.remove();
+                    let _ = self.tx.blocking_send(());
+                }
+            }
+            Action::ClearShares => {
+                self.share_info.clear();
+                let _ = self.tx.blocking_send(());
+            }
+            Action::ToggleReceiveDir => {
+                if self.current_block == CurrentBlock::Dir {
+                    self.toggle_receive_dir();
+                    let _ = self.tx.blocking_send(());
+                }
+            }
+            Action::CycleExpire => self.update_selected_share(Share::cycle_expire),
+            Action::CycleMaxDownloads => self.update_selected_share(Share::cycle_max_downloads),
+            Action::TogglePersist => self.update_selected_share(|s| s.persist = !s.persist),
+            Action::ToggleQr => self.toggle_qr(),
+            Action::CloseQr => self.qr_url = None,
+            Action::TogglePin => self.toggle_pin(),
+            Action::CancelTransfer => {
+                if self.current_block == CurrentBlock::Transfers {
+                    if let Some(transfer) = self.transfer_info.selected() {
+                        transfer.cancel();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 在当前列表中跳转, f 根据 (当前位置, 长度) 算出新位置, 列表不为空时才调用
+    fn jump(&mut self, f: impl FnOnce(usize, usize) -> usize) -> io::Result<()> {
+        match self.current_block {
+            CurrentBlock::Dir => self.dir_info.jump(f),
+            CurrentBlock::Shares => {
+                self.share_info.jump(f);
+                Ok(())
+            }
+            CurrentBlock::Transfers => {
+                self.transfer_info.jump(f);
+                Ok(())
+            }
+            CurrentBlock::Log => {
+                self.log_info.jump(f);
+                Ok(())
+            }
+        }
+    }
+
+    fn update_selected_share(&mut self, f: impl FnOnce(&mut Share)) {
+        if self.current_block == CurrentBlock::Shares {
+            self.share_info.update_selected(f);
+            let _ = self.tx.blocking_send(());
+        }
+    }
+
     fn get_current_block(&self) -> CurrentBlock {
         self.current_block
     }
@@ -138,6 +252,44 @@ impl App {
         self.current_block = target_block
     }
 
+    /// 把当前目录设为接收目录, 已经是接收目录则取消
+    fn toggle_receive_dir(&mut self) {
+        if let Some(current) = &self.dir_info.current {
+            let mut receive_info = self.receive_info.blocking_write();
+            if receive_info.dir.as_ref() == Some(&current.path) {
+                receive_info.dir = None;
+            } else {
+                receive_info.dir = Some(current.path.clone());
+            }
+        }
+    }
+
+    /// 开启 PIN 时每次生成新的 PIN, 已登录的浏览器需要重新登录
+    fn toggle_pin(&mut self) {
+        let mut auth = self.auth.blocking_write();
+        if auth.is_some() {
+            *auth = None;
+        } else {
+            *auth = Some(match &self.pin {
+                Some(pin) => Auth::new(pin.clone()),
+                None => Auth::random(),
+            });
+        }
+    }
+
+    /// 在 Shares 中显示选中分享的链接, 否则显示首页
+    fn toggle_qr(&mut self) {
+        if self.qr_url.is_some() {
+            self.qr_url = None;
+            return;
+        }
+        let link = match self.current_block {
+            CurrentBlock::Dir | CurrentBlock::Transfers | CurrentBlock::Log => None,
+            CurrentBlock::Shares => self.share_info.selected_link(),
+        };
+        self.qr_url = Some(format!("{}{}", self.visit.url, link.unwrap_or_default()));
+    }
+
     fn get_current_select_file(&self) -> Option<PathBuf> {
         if let Some(current) = &self.dir_info.current {
             if let Some(idx) = current.list_state.selected() {
@@ -182,6 +334,18 @@ impl DirInfo {
         Ok(())
     }
 
+    /// 目录内容有变化, 重新读取, 尽量保持原来的选中项
+    fn refresh(&mut self) -> io::Result<()> {
+        if let Some(current) = &self.current {
+            let (parent, current, child) =
+                gen_parent_current_child(current.path.clone(), &mut self.selected_map)?;
+            self.parent = parent;
+            self.current = current;
+            self.child = child;
+        }
+        Ok(())
+    }
+
     fn set_current_to_parent(&mut self) -> io::Result<()> {
         if let Some(parent) = &self.parent {
             self.set_current_dir(parent.path.clone())?;
@@ -209,6 +373,16 @@ impl DirInfo {
         Ok(())
     }
 
+    fn jump(&mut self, f: impl FnOnce(usize, usize) -> usize) -> io::Result<()> {
+        if let Some(current) = &self.current {
+            let len = current.files.len();
+            if let (Some(idx), true) = (current.list_state.selected(), len > 0) {
+                self.set_current_list_state(f(idx, len))?;
+            }
+        }
+        Ok(())
+    }
+
     fn set_current_list_state_prev(&mut self) -> io::Result<()> {
         if let Some(ref mut current) = self.current {
             let len = current.files.len();
@@ -261,11 +435,7 @@ fn gen_parent_current_child(
     let mut current = PathInfo::new(current_dir, PathType::Current)?;
     current.auto_select(selected_map);
     let child = if !current.files.is_empty() {
-        let selected_idx = if let Some(selected_idx) = current.list_state.selected() {
-            selected_idx
-        } else {
-            0
-        };
+        let selected_idx = current.list_state.selected().unwrap_or_default();
         let file = &current.files[selected_idx];
 
         if file.is_dir() {
@@ -314,7 +484,9 @@ impl PathInfo {
 
     fn auto_select(&mut self, selected_map: &HashMap<PathBuf, usize>) {
         if let Some(&idx) = selected_map.get(&self.path) {
-            self.list_state.select(Some(idx));
+            // 目录刷新后文件可能变少了
+            self.list_state
+                .select(Some(idx.min(self.files.len().saturating_sub(1))));
         }
     }
 
@@ -326,37 +498,40 @@ impl PathInfo {
 }
 
 struct ShareInfo {
-    path_arr: Arc<RwLock<Vec<PathBuf>>>,
+    share_arr: Arc<RwLock<Vec<Share>>>,
     list_state: ListState,
 }
 
 impl ShareInfo {
-    fn new(path_arr: Arc<RwLock<Vec<PathBuf>>>) -> Self {
+    fn new(share_arr: Arc<RwLock<Vec<Share>>>) -> Self {
         let mut list_state = ListState::default();
         list_state.select(Some(0));
         Self {
-            path_arr,
+            share_arr,
             list_state,
         }
     }
 
     fn add(&mut self, path_buf: PathBuf) {
-        let mut path_arr = self.path_arr.blocking_write();
-        if path_arr.contains(&path_buf) {
+        let mut share_arr = self.share_arr.blocking_write();
+        if share_arr.iter().any(|s| s.path == path_buf) {
             return;
         }
-        path_arr.push(path_buf);
-        sort_files(&mut path_arr);
+        share_arr.push(Share::new(path_buf));
+        share_arr.sort_by(|a, b| cmp_path(&a.path, &b.path));
         if self.list_state.selected().is_none() {
             self.list_state.select(Some(0));
         }
     }
 
     fn remove(&mut self) {
-        let mut path_arr = self.path_arr.blocking_write();
+        let mut share_arr = self.share_arr.blocking_write();
         if let Some(idx) = self.list_state.selected() {
-            path_arr.remove(idx);
-            let len = path_arr.len();
+            if idx >= share_arr.len() {
+                return;
+            }
+            share_arr.remove(idx);
+            let len = share_arr.len();
             if idx >= len {
                 if len > 0 {
                     self.list_state.select(Some(len - 1));
@@ -368,21 +543,21 @@ impl ShareInfo {
     }
 
     fn prev(&mut self) {
-        let path_arr = self.path_arr.blocking_read();
+        let share_arr = self.share_arr.blocking_read();
         if let Some(idx) = self.list_state.selected() {
-            let len = path_arr.len();
+            let len = share_arr.len();
             if idx > 0 {
                 self.list_state.select(Some(idx - 1));
-            } else {
+            } else if len > 0 {
                 self.list_state.select(Some(len - 1));
             }
         }
     }
 
     fn next(&mut self) {
-        let path_arr = self.path_arr.blocking_read();
+        let share_arr = self.share_arr.blocking_read();
         if let Some(idx) = self.list_state.selected() {
-            let len = path_arr.len();
+            let len = share_arr.len();
             if len <= 1 {
                 return;
             }
@@ -394,13 +569,144 @@ impl ShareInfo {
         }
     }
 
+    fn jump(&mut self, f: impl FnOnce(usize, usize) -> usize) {
+        let len = self.share_arr.blocking_read().len();
+        if let (Some(idx), true) = (self.list_state.selected(), len > 0) {
+            self.list_state.select(Some(f(idx, len)));
+        }
+    }
+
+    fn update_selected(&mut self, f: impl FnOnce(&mut Share)) {
+        let mut share_arr = self.share_arr.blocking_write();
+        if let Some(share) = self
+            .list_state
+            .selected()
+            .and_then(|idx| share_arr.get_mut(idx))
+        {
+            f(share);
+        }
+    }
+
+    fn selected_link(&self) -> Option<String> {
+        let share_arr = self.share_arr.blocking_read();
+        self.list_state
+            .selected()
+            .and_then(|idx| share_arr.get(idx))
+            .map(Share::link)
+    }
+
+    /// 分享可能被网页那边移除 (过期, 次数用完), 选中项不能越界
+    fn fix_selected(&mut self) {
+        let len = self.share_arr.blocking_read().len();
+        match self.list_state.selected() {
+            Some(idx) if idx >= len => self.list_state.select(len.checked_sub(1)),
+            None if len > 0 => self.list_state.select(Some(0)),
+            _ => {}
+        }
+    }
+
     fn clear(&mut self) {
-        let mut path_arr = self.path_arr.blocking_write();
-        path_arr.clear();
+        let mut share_arr = self.share_arr.blocking_write();
+        share_arr.clear();
         self.list_state.select(None);
     }
 }
 
+struct TransferInfo {
+    // 正在进行和最近结束的下载, 新的在后面
+    transfer_arr: Vec<Arc<Transfer>>,
+    // 按显示的顺序, 新的在上面
+    list_state: ListState,
+}
+
+impl TransferInfo {
+    fn new() -> Self {
+        Self {
+            transfer_arr: vec![],
+            list_state: ListState::default(),
+        }
+    }
+
+    /// 新的显示在最上面, 选中项跟着下移, 保持选中同一个下载
+    fn add(&mut self, transfer: Arc<Transfer>) {
+        self.transfer_arr.push(transfer);
+        let idx = self.list_state.selected().map_or(0, |idx| idx + 1);
+        self.list_state.select(Some(idx));
+    }
+
+    fn retain_recent(&mut self) {
+        let selected = self.selected().cloned();
+        transfer::retain_recent(&mut self.transfer_arr);
+        let len = self.transfer_arr.len();
+        let idx = selected
+            .and_then(|s| {
+                self.transfer_arr
+                    .iter()
+                    .rev()
+                    .position(|t| Arc::ptr_eq(t, &s))
+            })
+            .or(self
+                .list_state
+                .selected()
+                .map(|idx| idx.min(len.saturating_sub(1))));
+        self.list_state.select(idx.filter(|_| len > 0));
+    }
+
+    fn selected(&self) -> Option<&Arc<Transfer>> {
+        self.list_state
+            .selected()
+            .and_then(|idx| self.transfer_arr.iter().rev().nth(idx))
+    }
+
+    fn prev(&mut self) {
+        self.jump(|idx, len| (idx + len - 1) % len);
+    }
+
+    fn next(&mut self) {
+        self.jump(|idx, len| (idx + 1) % len);
+    }
+
+    fn jump(&mut self, f: impl FnOnce(usize, usize) -> usize) {
+        let len = self.transfer_arr.len();
+        if let (Some(idx), true) = (self.list_state.selected(), len > 0) {
+            self.list_state.select(Some(f(idx, len)));
+        }
+    }
+}
+
+struct LogInfo {
+    // 最近的访问, 新的在前面
+    entry_arr: VecDeque<AccessEntry>,
+    list_state: ListState,
+}
+
+impl LogInfo {
+    fn new() -> Self {
+        Self {
+            entry_arr: VecDeque::new(),
+            list_state: ListState::default(),
+        }
+    }
+
+    /// 选中最新一条时跟着新日志走, 否则保持选中同一条
+    fn add(&mut self, entry: AccessEntry) {
+        self.entry_arr.push_front(entry);
+        self.entry_arr.truncate(LOG_LEN);
+        let idx = match self.list_state.selected() {
+            None | Some(0) => 0,
+            Some(idx) => (idx + 1).min(self.entry_arr.len() - 1),
+        };
+        self.list_state.select(Some(idx));
+    }
+
+    fn jump(&mut self, f: impl FnOnce(usize, usize) -> usize) {
+        let len = self.entry_arr.len();
+        if let (Some(idx), true) = (self.list_state.selected(), len > 0) {
+            self.list_state.select(Some(f(idx, len)));
+        }
+    }
+}
+
 fn get_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
     files.clear();
     if dir.is_dir() {
@@ -435,7 +741,7 @@ fn path_last_n(path: &Path, n: usize) -> String {
     result
 }
 
-fn ui(frame: &mut Frame, app: &mut App, local_ip_addr: IpAddr) {
+fn ui(frame: &mut Frame, app: &mut App) {
     let main_layout = Layout::new(
         Direction::Vertical,
         [
@@ -446,11 +752,22 @@ fn ui(frame: &mut Frame, app: &mut App, local_ip_addr: IpAddr) {
     )
     .split(frame.size());
 
-    ui_title(frame, main_layout[0], local_ip_addr);
+    let pin = app
+        .auth
+        .blocking_read()
+        .as_ref()
+        .map(|auth| auth.pin.clone());
+    ui_title(frame, main_layout[0], &app.visit, pin);
 
+    // 去掉面板和列表的边框
+    app.page_height = main_layout[1].height.saturating_sub(4) as usize;
     ui_content(frame, main_layout[1], app);
 
-    ui_status_line(frame, main_layout[2]);
+    ui_status_line(frame, main_layout[2], &app.keymap);
+
+    if let Some(url) = &app.qr_url {
+        ui_qr(frame, frame.size(), url, &app.keymap);
+    }
 }
 
 fn ui_content(frame: &mut Frame, content_layout: Rect, app: &mut App) {
@@ -460,9 +777,31 @@ fn ui_content(frame: &mut Frame, content_layout: Rect, app: &mut App) {
     )
     .split(content_layout);
 
-    ui_dir(frame, inner_layout[0], app);
+    let left_layout = Layout::new(
+        Direction::Vertical,
+        [Constraint::Percentage(70), Constraint::Percentage(30)],
+    )
+    .split(inner_layout[0]);
 
-    ui_shares(frame, inner_layout[1], app);
+    ui_dir(frame, left_layout[0], app);
+
+    ui_log(frame, left_layout[1], app);
+
+    let right_layout = Layout::new(
+        Direction::Vertical,
+        [
+            Constraint::Percentage(40),
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+        ],
+    )
+    .split(inner_layout[1]);
+
+    ui_shares(frame, right_layout[0], app);
+
+    ui_transfers(frame, right_layout[1], app);
+
+    ui_received(frame, right_layout[2], app);
 }
 
 fn ui_dir(frame: &mut Frame, dir_block_layout: Rect, app: &mut App) {
@@ -486,12 +825,18 @@ fn ui_dir(frame: &mut Frame, dir_block_layout: Rect, app: &mut App) {
     )
     .split(dir_child);
 
-    ui_dir_files(frame, dir_layout[0], &mut app.dir_info.parent);
-    ui_dir_files(frame, dir_layout[1], &mut app.dir_info.current);
-    ui_dir_files(frame, dir_layout[2], &mut app.dir_info.child);
+    let colors = app.colors;
+    ui_dir_files(frame, dir_layout[0], &mut app.dir_info.parent, colors);
+    ui_dir_files(frame, dir_layout[1], &mut app.dir_info.current, colors);
+    ui_dir_files(frame, dir_layout[2], &mut app.dir_info.child, colors);
 }
 
-fn ui_dir_files(frame: &mut Frame, dir_layout: Rect, path_info: &mut Option<PathInfo>) {
+fn ui_dir_files(
+    frame: &mut Frame,
+    dir_layout: Rect,
+    path_info: &mut Option<PathInfo>,
+    colors: Colors,
+) {
     if let Some(path_info) = path_info {
         let title = match path_info.path_type {
             PathType::Parent => "Parent",
@@ -503,7 +848,7 @@ fn ui_dir_files(frame: &mut Frame, dir_layout: Rect, path_info: &mut Option<Path
             .iter()
             .map(|p| {
                 let lines = vec![path_last_n(p, 2).into()];
-                ListItem::new(lines).style(Style::default().fg(COLOR_FG).bg(COLOR_BG))
+                ListItem::new(lines).style(Style::default().fg(colors.fg).bg(colors.bg))
             })
             .collect();
         let dir_list = List::new(items)
@@ -514,7 +859,7 @@ fn ui_dir_files(frame: &mut Frame, dir_layout: Rect, path_info: &mut Option<Path
             )
             .highlight_style(
                 Style::default()
-                    .bg(COLOR_HIGHLIGHT)
+                    .bg(colors.highlight)
                     .add_modifier(Modifier::BOLD),
             )
             .direction(ListDirection::TopToBottom);
@@ -527,57 +872,266 @@ fn ui_shares(frame: &mut Frame, share_layout: Rect, app: &mut App) {
     if app.get_current_block() == CurrentBlock::Shares {
         block = block.style(Style::new().fg(Color::Yellow).bold());
     }
+    app.share_info.fix_selected();
     let items: Vec<ListItem> = app
         .share_info
-        .path_arr
+        .share_arr
         .blocking_read()
         .iter()
-        .map(|p| {
-            let lines = vec![path_last_n(p, 2).into()];
-            ListItem::new(lines).style(Style::default().fg(COLOR_FG).bg(COLOR_BG))
+        .map(|s| {
+            let mut line = path_last_n(&s.path, 2);
+            if let Some(limit) = s.limit_desc() {
+                line.push_str(&format!(" [{limit}]"));
+            }
+            if !s.persist {
+                line.push_str(" [temp]");
+            }
+            let lines = vec![line.into()];
+            ListItem::new(lines).style(Style::default().fg(app.colors.fg).bg(app.colors.bg))
         })
         .collect();
     let dir_list = List::new(items)
         .block(block)
         .highlight_style(
             Style::default()
-                .bg(COLOR_HIGHLIGHT)
+                .bg(app.colors.highlight)
                 .add_modifier(Modifier::BOLD),
         )
         .direction(ListDirection::TopToBottom);
     frame.render_stateful_widget(dir_list, share_layout, &mut app.share_info.list_state);
 }
 
-fn ui_title(frame: &mut Frame, title_layout: Rect, local_ip_addr: IpAddr) {
-    let title = Span::styled(
-        format!("Visit {}:{PORT}", local_ip_addr),
-        Style::new()
-            .fg(Color::LightBlue)
-            .add_modifier(Modifier::BOLD),
+/// 每个下载一行进度条, 新的在上面
+fn ui_transfers(frame: &mut Frame, transfers_layout: Rect, app: &mut App) {
+    let mut block = Block::bordered().title("Transfers");
+    let is_focus = app.get_current_block() == CurrentBlock::Transfers;
+    if is_focus {
+        block = block.style(Style::new().fg(Color::Yellow).bold());
+    }
+    let inner = block.inner(transfers_layout);
+    frame.render_widget(block, transfers_layout);
+
+    let row_arr = Layout::new(
+        Direction::Vertical,
+        vec![Constraint::Length(1); inner.height as usize],
+    )
+    .split(inner);
+    // 选中项超出显示范围时往下滚
+    let selected = app.transfer_info.list_state.selected();
+    let offset = selected.map_or(0, |idx| (idx + 1).saturating_sub(row_arr.len()));
+    let transfer_iter = app.transfer_info.transfer_arr.iter().rev().enumerate();
+    for ((idx, transfer), row) in transfer_iter.skip(offset).zip(row_arr.iter()) {
+        let (state, color) = match transfer.state() {
+            TransferState::Running => {
+                (format!("{}/s", format_size(transfer.speed())), Color::Green)
+            }
+            TransferState::Stalled => ("stalled".to_string(), Color::Yellow),
+            TransferState::Done => ("done".to_string(), Color::Blue),
+            TransferState::Aborted => ("aborted".to_string(), Color::Red),
+            TransferState::Canceled => ("canceled".to_string(), Color::Red),
+        };
+        let size = match transfer.total {
+            Some(total) => format!("{}/{}", format_size(transfer.sent()), format_size(total)),
+            None => format_size(transfer.sent()),
+        };
+        let label = format!("{} {} {size} {state}", transfer.name, transfer.client);
+        let label_style = if is_focus && selected == Some(idx) {
+            Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        // 不知道总大小的打包下载, 发完之前进度条一直是空的
+        let ratio = transfer
+            .ratio()
+            .unwrap_or(if transfer.is_finished() { 1.0 } else { 0.0 });
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(color).bg(app.colors.bg))
+            .ratio(ratio)
+            .label(Span::styled(label, label_style));
+        frame.render_widget(gauge, *row);
+    }
+}
+
+/// 最近的访问, 新的在上面: 时间 客户端 方法 路径 状态 大小 分享 UA
+fn ui_log(frame: &mut Frame, log_layout: Rect, app: &mut App) {
+    let mut block = Block::bordered().title("Log");
+    if app.get_current_block() == CurrentBlock::Log {
+        block = block.style(Style::new().fg(Color::Yellow).bold());
+    }
+    let items: Vec<ListItem> = app
+        .log_info
+        .entry_arr
+        .iter()
+        .map(|e| {
+            let status_color = match e.status {
+                200..=399 => app.colors.fg,
+                400..=499 => Color::Yellow,
+                _ => Color::Red,
+            };
+            let line = Line::from(vec![
+                Span::raw(format!(
+                    "{} {} {} {} ",
+                    e.time.format("%H:%M:%S"),
+                    e.client,
+                    e.method,
+                    e.path
+                )),
+                Span::styled(e.status.to_string(), Style::default().fg(status_color)),
+                Span::raw(format!(
+                    " {} {} {}",
+                    format_size(e.bytes),
+                    e.share.as_deref().unwrap_or("-"),
+                    e.user_agent
+                )),
+            ]);
+            ListItem::new(line).style(Style::default().fg(app.colors.fg).bg(app.colors.bg))
+        })
+        .collect();
+    let log_list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(app.colors.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .direction(ListDirection::TopToBottom);
+    frame.render_stateful_widget(log_list, log_layout, &mut app.log_info.list_state);
+}
+
+fn ui_received(frame: &mut Frame, received_layout: Rect, app: &mut App) {
+    let receive_info = app.receive_info.blocking_read();
+    let title = match &receive_info.dir {
+        Some(dir) => format!("Received -> {}", path_last_n(dir, 2)),
+        None => "Received (off)".to_string(),
+    };
+    // 最新的在最上面
+    let items: Vec<ListItem> = receive_info
+        .files
+        .iter()
+        .rev()
+        .map(|p| {
+            let lines = vec![path_last_n(p, 1).into()];
+            ListItem::new(lines).style(Style::default().fg(app.colors.fg).bg(app.colors.bg))
+        })
+        .collect();
+    let received_list = List::new(items)
+        .block(Block::bordered().title(title))
+        .direction(ListDirection::TopToBottom);
+    frame.render_widget(received_list, received_layout);
+}
+
+/// 用半格字符画二维码, 一个字符上下两个模块, 浮在界面中间
+fn ui_qr(frame: &mut Frame, area: Rect, url: &str, keymap: &Keymap) {
+    let Ok(code) = QrCode::new(url.as_bytes()) else {
+        return;
+    };
+    let image = code.render::<Dense1x2>().quiet_zone(true).build();
+    let lines: Vec<Line> = image.lines().map(Line::from).collect();
+
+    let qr_width = lines.iter().map(|l| l.width()).max().unwrap_or_default() as u16;
+    let width = qr_width.max(url.len() as u16) + 2;
+    let height = lines.len() as u16 + 2;
+    let block = Block::bordered()
+        .title(url.to_string())
+        .title_bottom(format!(
+            "'{}'/'{}' close",
+            keymap.key_of(Action::ToggleQr),
+            keymap.key_of(Action::CloseQr)
+        ));
+
+    if width > area.width || height > area.height {
+        let area = centered_rect(area, url.len() as u16 + 2, 3);
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new("terminal too small").block(block), area);
+        return;
+    }
+
+    let area = centered_rect(area, width, height);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .style(Style::new().fg(Color::Black).bg(Color::White))
+            .block(block),
+        area,
     );
-    let title = Line::from(vec![title]);
+}
+
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn ui_title(frame: &mut Frame, title_layout: Rect, visit: &Visit, pin: Option<String>) {
+    let style = Style::new()
+        .fg(Color::LightBlue)
+        .add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::styled(format!("Visit {}", visit.url), style)];
+    if let Some(pin) = pin {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(format!("PIN {pin}"), style.fg(Color::Yellow)));
+    }
+    if let Some(fingerprint) = &visit.fingerprint {
+        spans.push(Span::raw("  "));
+        spans.push(Span::raw(format!("SHA-256 {fingerprint}")));
+    }
+    let title = Line::from(spans);
     let text: Text = Text::from(vec![title]);
 
     frame.render_widget(Paragraph::new(text), title_layout);
 }
 
-fn ui_status_line(frame: &mut Frame, status_layout: Rect) {
+fn ui_status_line(frame: &mut Frame, status_layout: Rect, keymap: &Keymap) {
     let style_key = Style::new()
         .fg(Color::Green)
         .bg(Color::Black)
         .add_modifier(Modifier::BOLD);
 
+    // 按键随配置变化, 每个动作只显示第一个按键
+    let key = |action_arr: &[Action]| {
+        let key_arr: Vec<String> = action_arr
+            .iter()
+            .map(|&action| format!("'{}'", keymap.key_of(action)))
+            .collect();
+        Span::styled(key_arr.join("/"), style_key)
+    };
+
     let line = Line::from(vec![
         Span::raw("Press "),
-        Span::styled("'Q'", style_key),
+        key(&[Action::Quit]),
         Span::raw(" to exit, "),
-        Span::styled("'ctrl + h'/'ctrl + l'", style_key),
+        key(&[Action::SwitchPanel]),
         Span::raw(" switch panel, "),
-        Span::styled("'h'/'j'/'k'/'l'", style_key),
+        key(&[
+            Action::ParentDir,
+            Action::MoveDown,
+            Action::MoveUp,
+            Action::EnterDir,
+        ]),
         Span::raw(" to select file, "),
-        Span::styled("'='/'-'", style_key),
+        key(&[Action::MoveTop, Action::MoveBottom]),
+        Span::raw(" top/bottom, "),
+        key(&[Action::AddShare, Action::RemoveShare]),
         Span::raw(" add/remove share, "),
-        Span::styled("'C'", style_key),
+        key(&[Action::ToggleReceiveDir]),
+        Span::raw(" receive uploads here, "),
+        key(&[Action::CycleExpire, Action::CycleMaxDownloads]),
+        Span::raw(" share expire time/max downloads, "),
+        key(&[Action::TogglePersist]),
+        Span::raw(" keep share after restart, "),
+        key(&[Action::ToggleQr]),
+        Span::raw(" QR code, "),
+        key(&[Action::TogglePin]),
+        Span::raw(" toggle PIN, "),
+        key(&[Action::CancelTransfer]),
+        Span::raw(" cancel transfer, "),
+        key(&[Action::ClearShares]),
         Span::raw(" clear all shares."),
     ]);
     let text: Text = Text::from(vec![line]);
@@ -0,0 +1,73 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{body::Bytes, BoxError};
+use futures::{Future, Stream};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+/// 包装响应的 body, 每发出一块数据调用 on_chunk,
+/// 被丢弃时 (发完或者连接断开) 把总字节数交给 on_finish
+pub struct CountingStream<S> {
+    inner: S,
+    bytes: u64,
+    // 在这里等, 下一块数据还没好时取消也能马上结束
+    cancelled: Option<Pin<Box<WaitForCancellationFutureOwned>>>,
+    on_chunk: Box<dyn FnMut(usize) + Send>,
+    on_finish: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl<S> CountingStream<S> {
+    pub fn new(
+        inner: S,
+        on_chunk: impl FnMut(usize) + Send + 'static,
+        on_finish: impl FnOnce(u64) + Send + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            bytes: 0,
+            cancelled: None,
+            on_chunk: Box::new(on_chunk),
+            on_finish: Some(Box::new(on_finish)),
+        }
+    }
+
+    /// token 取消后断开连接
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancelled = Some(Box::pin(cancel.cancelled_owned()));
+        self
+    }
+}
+
+impl<S, E> Stream for CountingStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<BoxError>,
+{
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // 返回错误, hyper 会直接断开连接, 对方知道下载没有完成
+        if let Some(cancelled) = &mut self.cancelled {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Some(Err("transfer canceled".into())));
+            }
+        }
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        if let Poll::Ready(Some(Ok(bytes))) = &poll {
+            let len = bytes.len();
+            self.bytes += len as u64;
+            (self.on_chunk)(len);
+        }
+        poll.map(|item| item.map(|result| result.map_err(Into::into)))
+    }
+}
+
+impl<S> Drop for CountingStream<S> {
+    fn drop(&mut self) {
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(self.bytes);
+        }
+    }
+}
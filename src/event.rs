@@ -0,0 +1,95 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{mpsc::Sender, Arc},
+    thread,
+    time::Duration,
+};
+
+use crossterm::event::{self, Event};
+use notify::{event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{access_log::AccessEntry, transfer::Transfer};
+
+/// TUI 主循环收到的事件, 来自终端输入, web 服务, 定时器和文件监听
+#[derive(Debug)]
+pub enum AppEvent {
+    Input(Event),
+    /// web 那边的分享, 接收文件等有变化
+    Changed,
+    /// 定时刷新, 如过期倒计时
+    Tick,
+    /// 当前目录里有文件增删或改名
+    DirChanged,
+    /// 开始了一次下载
+    Transfer(Arc<Transfer>),
+    /// 一次请求结束
+    Access(AccessEntry),
+}
+
+/// 读终端输入的线程, 主循环退出后随进程结束
+pub fn spawn_input(tx: Sender<AppEvent>) {
+    thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if tx.send(AppEvent::Input(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+pub fn spawn_tick(tx: Sender<AppEvent>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        if tx.send(AppEvent::Tick).is_err() {
+            break;
+        }
+    });
+}
+
+/// 监听 Dir 面板当前所在的目录, 只关心增删和改名, 写文件内容不管
+pub struct DirWatcher {
+    watcher: Option<RecommendedWatcher>,
+    path: Option<PathBuf>,
+}
+
+impl DirWatcher {
+    pub fn new(tx: Sender<AppEvent>) -> Self {
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            if matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                let _ = tx.send(AppEvent::DirChanged);
+            }
+        });
+        if let Err(e) = &watcher {
+            tracing::warn!("can not watch dir: {e}");
+        }
+        Self {
+            watcher: watcher.ok(),
+            path: None,
+        }
+    }
+
+    /// 换到新的目录, 和正在监听的相同则不变
+    pub fn watch(&mut self, path: &Path) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if self.path.as_deref() == Some(path) {
+            return;
+        }
+        if let Some(old) = self.path.take() {
+            let _ = watcher.unwatch(&old);
+        }
+        match watcher.watch(path, RecursiveMode::NonRecursive) {
+            Ok(()) => self.path = Some(path.to_path_buf()),
+            Err(e) => tracing::warn!("can not watch {}: {e}", path.display()),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{net::IpAddr, str::FromStr};
+
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer};
+
+/// 网段, 单个 ip 当作 /32 或 /128
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr(IpNet);
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<IpNet>()
+            .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+            .map(Cidr)
+            .map_err(|_| format!("invalid ip or cidr `{s}`"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// 先看 deny, 再看 allow, allow 为空时不限制
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl IpFilter {
+    pub fn new(allow: Vec<Cidr>, deny: Vec<Cidr>) -> Self {
+        Self { allow, deny }
+    }
+
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        // ipv6 监听时 ipv4 的客户端是 ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        let contains = |cidr: &Cidr| cidr.0.contains(&ip);
+        if self.deny.iter().any(contains) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(contains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip_filter(allow: &[&str], deny: &[&str]) -> IpFilter {
+        let parse = |arr: &[&str]| arr.iter().map(|s| s.parse().unwrap()).collect();
+        IpFilter::new(parse(allow), parse(deny))
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_cidr() {
+        assert!("192.168.1.0/24".parse::<Cidr>().is_ok());
+        assert!("fe80::/10".parse::<Cidr>().is_ok());
+        assert_eq!(
+            "10.0.0.1".parse::<Cidr>().unwrap(),
+            "10.0.0.1/32".parse::<Cidr>().unwrap()
+        );
+        assert!("10.0.0.1/33".parse::<Cidr>().is_err());
+        assert!("localhost".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn empty_allow_allows_all() {
+        let filter = ip_filter(&[], &[]);
+        assert!(filter.is_allowed(ip("8.8.8.8")));
+        assert!(filter.is_allowed(ip("::1")));
+    }
+
+    #[test]
+    fn allow_subnet_and_single_ip() {
+        let filter = ip_filter(&["192.168.1.0/24", "10.0.0.1"], &[]);
+        assert!(filter.is_allowed(ip("192.168.1.20")));
+        assert!(filter.is_allowed(ip("10.0.0.1")));
+        assert!(!filter.is_allowed(ip("10.0.0.2")));
+        assert!(!filter.is_allowed(ip("192.168.2.1")));
+    }
+
+    #[test]
+    fn deny_before_allow() {
+        let filter = ip_filter(&["192.168.1.0/24"], &["192.168.1.13"]);
+        assert!(!filter.is_allowed(ip("192.168.1.13")));
+        assert!(filter.is_allowed(ip("192.168.1.14")));
+
+        // 只有 deny 时其它都放行
+        let filter = ip_filter(&[], &["192.168.1.0/24"]);
+        assert!(!filter.is_allowed(ip("192.168.1.1")));
+        assert!(filter.is_allowed(ip("192.168.2.1")));
+    }
+
+    #[test]
+    fn ipv4_mapped_ipv6() {
+        let filter = ip_filter(&["192.168.1.0/24"], &["192.168.1.13"]);
+        assert!(filter.is_allowed(ip("::ffff:192.168.1.20")));
+        assert!(!filter.is_allowed(ip("::ffff:192.168.1.13")));
+        assert!(!filter.is_allowed(ip("::ffff:10.0.0.1")));
+    }
+}
@@ -0,0 +1,306 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Deserializer};
+
+/// 按键触发的动作, 配置文件里用 snake_case 的名字
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    SwitchPanel,
+    MoveUp,
+    MoveDown,
+    MoveTop,
+    MoveBottom,
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    ParentDir,
+    EnterDir,
+    AddShare,
+    RemoveShare,
+    ClearShares,
+    ToggleReceiveDir,
+    CycleExpire,
+    CycleMaxDownloads,
+    TogglePersist,
+    ToggleQr,
+    CloseQr,
+    TogglePin,
+    CancelTransfer,
+}
+
+// 默认按键, vim 风格加上方向键
+const DEFAULT_KEYS: &[(Action, &[&str])] = &[
+    (Action::Quit, &["Q"]),
+    (Action::SwitchPanel, &["ctrl-h", "ctrl-l", "tab"]),
+    (Action::MoveUp, &["k", "up"]),
+    (Action::MoveDown, &["j", "down"]),
+    (Action::MoveTop, &["g g", "home"]),
+    (Action::MoveBottom, &["G", "end"]),
+    (Action::HalfPageUp, &["ctrl-u"]),
+    (Action::HalfPageDown, &["ctrl-d"]),
+    (Action::PageUp, &["pageup"]),
+    (Action::PageDown, &["pagedown"]),
+    (Action::ParentDir, &["h", "left", "backspace"]),
+    (Action::EnterDir, &["l", "right", "enter"]),
+    (Action::AddShare, &["="]),
+    (Action::RemoveShare, &["-", "delete"]),
+    (Action::ClearShares, &["C"]),
+    (Action::ToggleReceiveDir, &["R"]),
+    (Action::CycleExpire, &["t"]),
+    (Action::CycleMaxDownloads, &["n"]),
+    (Action::TogglePersist, &["s"]),
+    (Action::ToggleQr, &["v"]),
+    (Action::CloseQr, &["esc"]),
+    (Action::TogglePin, &["P"]),
+    (Action::CancelTransfer, &["x"]),
+];
+
+/// 一个按键, 字符键不看 shift, 大小写已经体现在字符上
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self { code, modifiers }
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    /// `j`, `G`, `ctrl-d`, `alt-enter`, `pageup`, `f1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        // `-` 本身也是一个按键, 只有后面还有内容时才当作分隔符
+        while let Some((prefix, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => break,
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "space" => KeyCode::Char(' '),
+                f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => return Err(format!("invalid key `{s}`")),
+                },
+            },
+        };
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+/// 按顺序按下的几个键, 用空格分隔, 如 `g g`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySeq {
+    keys: Vec<Key>,
+    // 原样保留, 显示在状态栏
+    text: String,
+}
+
+impl FromStr for KeySeq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split_whitespace()
+            .map(Key::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err("empty key".to_string());
+        }
+        Ok(Self {
+            keys,
+            text: s.split_whitespace().collect::<Vec<_>>().join(" "),
+        })
+    }
+}
+
+impl fmt::Display for KeySeq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeySeq {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// 按键到动作的映射, 配置文件里设置了的动作整个替换默认按键
+pub struct Keymap {
+    binding_arr: Vec<(KeySeq, Action)>,
+    // 已经按下, 但还没有匹配完的键, 如 `gg` 的第一个 `g`
+    pending: Vec<Key>,
+}
+
+impl Keymap {
+    pub fn new(custom: &HashMap<Action, Vec<KeySeq>>) -> Self {
+        let mut binding_arr = vec![];
+        for (action, keys) in DEFAULT_KEYS {
+            match custom.get(action) {
+                Some(seq_arr) => {
+                    binding_arr.extend(seq_arr.iter().map(|seq| (seq.clone(), *action)))
+                }
+                None => binding_arr.extend(
+                    keys.iter()
+                        .map(|k| (k.parse().expect("invalid default key"), *action)),
+                ),
+            }
+        }
+        Self {
+            binding_arr,
+            pending: vec![],
+        }
+    }
+
+    /// 输入一个按键, 匹配到完整的按键序列时返回动作
+    pub fn resolve(&mut self, event: KeyEvent) -> Option<Action> {
+        self.pending.push(Key::new(event.code, event.modifiers));
+        if let Some(action) = self.match_pending() {
+            return action;
+        }
+        // 前面的键组不成序列, 只用这一个键重新匹配
+        self.pending.drain(..self.pending.len() - 1);
+        self.match_pending().unwrap_or_else(|| {
+            self.pending.clear();
+            None
+        })
+    }
+
+    /// 完整匹配返回 Some(Some), 是某个序列的前缀返回 Some(None) 继续等
+    fn match_pending(&mut self) -> Option<Option<Action>> {
+        if let Some((_, action)) = self
+            .binding_arr
+            .iter()
+            .find(|(seq, _)| seq.keys == self.pending)
+        {
+            self.pending.clear();
+            return Some(Some(*action));
+        }
+        self.binding_arr
+            .iter()
+            .any(|(seq, _)| seq.keys.starts_with(&self.pending))
+            .then_some(None)
+    }
+
+    /// 动作的第一个按键, 用在状态栏的提示
+    pub fn key_of(&self, action: Action) -> String {
+        self.binding_arr
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(seq, _)| seq.to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(keymap: &mut Keymap, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        keymap.resolve(KeyEvent::new(code, modifiers))
+    }
+
+    fn char(keymap: &mut Keymap, c: char) -> Option<Action> {
+        // 终端里大写字母带着 shift
+        let modifiers = if c.is_ascii_uppercase() {
+            KeyModifiers::SHIFT
+        } else {
+            KeyModifiers::NONE
+        };
+        press(keymap, KeyCode::Char(c), modifiers)
+    }
+
+    #[test]
+    fn parse_key() {
+        assert_eq!(
+            "ctrl-d".parse::<Key>().unwrap(),
+            Key::new(KeyCode::Char('d'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            "-".parse::<Key>().unwrap(),
+            Key::new(KeyCode::Char('-'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            "alt--".parse::<Key>().unwrap(),
+            Key::new(KeyCode::Char('-'), KeyModifiers::ALT)
+        );
+        assert_eq!("F5".parse::<Key>().unwrap().code, KeyCode::F(5));
+        assert!("ctrl-nope".parse::<Key>().is_err());
+        assert!("  ".parse::<KeySeq>().is_err());
+    }
+
+    #[test]
+    fn sequence_and_single_key() {
+        let mut keymap = Keymap::new(&HashMap::new());
+        assert_eq!(char(&mut keymap, 'g'), None);
+        assert_eq!(char(&mut keymap, 'g'), Some(Action::MoveTop));
+        assert_eq!(char(&mut keymap, 'G'), Some(Action::MoveBottom));
+        assert_eq!(
+            press(&mut keymap, KeyCode::Char('d'), KeyModifiers::CONTROL),
+            Some(Action::HalfPageDown)
+        );
+        assert_eq!(char(&mut keymap, 'z'), None);
+    }
+
+    #[test]
+    fn prefix_falls_back_to_single_key() {
+        let mut keymap = Keymap::new(&HashMap::new());
+        assert_eq!(char(&mut keymap, 'g'), None);
+        // `g j` 不是序列, 按 `j` 重新匹配
+        assert_eq!(char(&mut keymap, 'j'), Some(Action::MoveDown));
+        assert_eq!(char(&mut keymap, 'g'), None);
+        assert_eq!(char(&mut keymap, 'z'), None);
+        assert_eq!(char(&mut keymap, 'j'), Some(Action::MoveDown));
+    }
+
+    #[test]
+    fn custom_keys_replace_defaults() {
+        let custom = HashMap::from([(Action::MoveDown, vec!["ctrl-n".parse().unwrap()])]);
+        let mut keymap = Keymap::new(&custom);
+        assert_eq!(char(&mut keymap, 'j'), None);
+        assert_eq!(
+            press(&mut keymap, KeyCode::Char('n'), KeyModifiers::CONTROL),
+            Some(Action::MoveDown)
+        );
+        assert_eq!(keymap.key_of(Action::MoveDown), "ctrl-n");
+        assert_eq!(keymap.key_of(Action::MoveUp), "k");
+    }
+}
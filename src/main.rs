@@ -1,44 +1,215 @@
+mod access_log;
+mod archive;
+mod assets;
+mod auth;
+mod cli;
+mod config;
 mod console_ui;
 mod consts;
+mod counting;
+mod event;
+mod ip_filter;
+mod keymap;
+mod preview;
+mod range;
+mod receive;
+mod share;
+mod store;
+mod thumb;
+mod tls;
+mod transfer;
 mod utils;
 mod web;
 
 use std::{
     env::current_dir,
     io::{self, stdout},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener},
+    path::PathBuf,
+    process::exit,
     sync::Arc,
 };
 
-use console_ui::{run_app, App};
+use auth::Auth;
+use clap::Parser;
+use cli::Args;
+use config::Config;
+use consts::PORT;
+use ip_filter::IpFilter;
+
+use console_ui::{run_app, App, Visit};
 use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
+use local_ip_address::local_ip;
 use ratatui::prelude::*;
+use receive::ReceiveInfo;
+use share::Share;
+use tls::Tls;
 use tokio::sync::{mpsc, oneshot, RwLock};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{filter::filter_fn, prelude::*, EnvFilter};
+use web::ServeOptions;
 
 fn main() -> io::Result<()> {
-    let file_appender = RollingFileAppender::new(Rotation::DAILY, "log", "my_app.log");
+    let args = Args::parse();
 
-    // 设置 tracing 订阅者，将日志输出到文件
-    tracing_subscriber::fmt().with_writer(file_appender).init();
+    // 先在终端里检查参数, 进了 TUI 之后错误就看不到了
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            exit(1);
+        }
+    };
+
+    if let Err(e) = init_log(&args, &config) {
+        eprintln!("error: {e}");
+        exit(1);
+    }
+
+    // 命令行参数优先于配置文件
+    let port = args.port.or(config.port).unwrap_or(PORT);
+    let bind = args
+        .bind
+        .or(config.bind)
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
 
-    let share_path_arr = Arc::new(RwLock::new(vec![]));
+    // headless 默认只分享参数和配置文件里的, 也不覆盖 TUI 保存的分享
+    let is_restore = !args.headless || args.restore;
+    let share_arr = match init_shares(
+        &[config.shares.clone(), args.paths.clone()].concat(),
+        is_restore,
+    ) {
+        Ok(share_arr) => share_arr,
+        Err(e) => {
+            eprintln!("error: {e}");
+            exit(1);
+        }
+    };
+    let listener = match TcpListener::bind((bind, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("error: can not listen on {bind}:{port}: {e}");
+            exit(1);
+        }
+    };
+    listener.set_nonblocking(true)?;
+    let visit_addr = visit_addr(bind, listener.local_addr()?.port());
+
+    let tls = match init_tls(&args, &config, bind, visit_addr) {
+        Ok(tls) => tls,
+        Err(e) => {
+            eprintln!("error: {e}");
+            exit(1);
+        }
+    };
+    let visit = Visit {
+        url: match tls {
+            Some(_) => format!("https://{visit_addr}"),
+            None => format!("http://{visit_addr}"),
+        },
+        fingerprint: tls.as_ref().map(|tls| tls.fingerprint.clone()),
+    };
+
+    let share_path_arr = Arc::new(RwLock::new(share_arr));
+    let receive_info = Arc::new(RwLock::new(ReceiveInfo::default()));
+    let auth = Arc::new(RwLock::new(config.auth.enabled.then(
+        || match &config.auth.pin {
+            Some(pin) => Auth::new(pin.clone()),
+            None => Auth::random(),
+        },
+    )));
     let (tx, rx) = mpsc::channel(16);
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    // web 那边的变化通知 TUI 重绘
+    let (event_tx, event_rx) = std::sync::mpsc::channel();
 
-    web::run(rx, share_path_arr.clone(), shutdown_rx);
+    // 命令行给了就整个替换配置文件里的
+    let ip_filter = IpFilter::new(
+        if args.allow.is_empty() {
+            config.allow.clone()
+        } else {
+            args.allow.clone()
+        },
+        if args.deny.is_empty() {
+            config.deny.clone()
+        } else {
+            args.deny.clone()
+        },
+    );
+    let web_handle = web::run(
+        ServeOptions {
+            listener,
+            ip_filter,
+            tls: tls.map(|tls| tls.config),
+            is_save: is_restore,
+        },
+        rx,
+        share_path_arr.clone(),
+        receive_info.clone(),
+        auth.clone(),
+        event_tx.clone(),
+        shutdown_rx,
+    );
+
+    if args.headless {
+        // 没有 TUI, 不需要事件
+        drop(event_rx);
+        for share in share_path_arr.blocking_read().iter() {
+            println!("{}: {}{}", share.path.display(), visit.url, share.link());
+        }
+        println!("Visit {}, press Ctrl-C to stop", visit.url);
+        if let Some(fingerprint) = &visit.fingerprint {
+            println!("Certificate SHA-256 {fingerprint}");
+        }
+        if let Some(auth) = auth.blocking_read().as_ref() {
+            println!("PIN {}", auth.pin);
+        }
+        tracing::info!("headless, visit {}", visit.url);
+
+        let result = wait_for_signal();
+        println!("Stopping, press Ctrl-C again to quit now");
+        // 再来一次信号就不等进行中的下载了
+        let share_arr_clone = share_path_arr.clone();
+        std::thread::spawn(move || {
+            let _ = wait_for_signal();
+            if is_restore {
+                store::save_shares(&share_arr_clone.blocking_read());
+            }
+            exit(130);
+        });
+        let _ = shutdown_tx.send(());
+        let _ = web_handle.join();
+        if is_restore {
+            store::save_shares(&share_path_arr.blocking_read());
+        }
+        return result;
+    }
+
+    let start_dir = match args.dir.or(config.dir.clone()) {
+        Some(dir) => dir.canonicalize(),
+        None => current_dir(),
+    };
 
-    let result = match current_dir() {
+    let result = match start_dir {
         Ok(dir) => {
             enable_raw_mode()?;
             stdout().execute(EnterAlternateScreen)?;
             let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
-            let app = App::new(tx, dir, share_path_arr)?;
+            let app = App::new(
+                tx,
+                dir,
+                share_path_arr.clone(),
+                receive_info,
+                auth,
+                visit,
+                &config,
+            )?;
 
-            run_app(&mut terminal, app)?;
+            run_app(&mut terminal, app, event_tx, event_rx)?;
 
             stdout().execute(LeaveAlternateScreen)?;
             disable_raw_mode()?;
@@ -49,6 +220,135 @@ fn main() -> io::Result<()> {
 
     // tell axum shutdown
     let _ = shutdown_tx.send(());
+    store::save_shares(&share_path_arr.blocking_read());
 
     result
 }
+
+/// 上次保存的分享 (is_restore 时), 加上配置文件和参数里的文件或目录, 后者不存在直接报错
+fn init_shares(paths: &[PathBuf], is_restore: bool) -> io::Result<Vec<Share>> {
+    let mut share_arr = if is_restore {
+        store::load_shares()
+    } else {
+        vec![]
+    };
+    for path in paths {
+        let path = path
+            .canonicalize()
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        if !share_arr.iter().any(|s| s.path == path) {
+            share_arr.push(Share::new(path));
+        }
+    }
+    share_arr.sort_by(|a, b| utils::cmp_path(&a.path, &b.path));
+    Ok(share_arr)
+}
+
+/// 命令行或配置文件开启了 HTTPS 时加载证书, 自签名证书对访问地址和 localhost 有效.
+/// 命令行的证书隐含 --tls, 配置文件的证书要 `enabled = true` 才用, 两边都有时命令行优先
+fn init_tls(
+    args: &Args,
+    config: &Config,
+    bind: IpAddr,
+    visit_addr: SocketAddr,
+) -> io::Result<Option<Tls>> {
+    if !args.tls && args.tls_cert.is_none() && !config.tls.enabled {
+        return Ok(None);
+    }
+    let cert_key = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some((cert.as_path(), key.as_path())),
+        _ => match (&config.tls.cert, &config.tls.key) {
+            (Some(cert), Some(key)) => Some((cert.as_path(), key.as_path())),
+            _ => None,
+        },
+    };
+    let mut hosts = vec!["localhost".to_string(), visit_addr.ip().to_string()];
+    if !bind.is_unspecified() && bind != visit_addr.ip() {
+        hosts.push(bind.to_string());
+    }
+    Tls::load(cert_key, hosts).map(Some)
+}
+
+/// 网页的访问地址, 监听所有网卡时用局域网 ip
+fn visit_addr(bind: IpAddr, port: u16) -> SocketAddr {
+    let ip = if bind.is_unspecified() {
+        local_ip().unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    } else {
+        bind
+    };
+    SocketAddr::new(ip, port)
+}
+
+/// 日志级别: --log-level > RUST_LOG > 配置文件 > info,
+/// 访问日志不受级别影响, 单独写成 JSON lines
+fn init_log(args: &Args, config: &Config) -> Result<(), String> {
+    let is_log_file = !args.no_log_file && config.log_file.unwrap_or(true);
+    let log_dir = args.log_dir.clone().or(config.log_dir.clone()).or_else(|| {
+        dirs::state_dir()
+            .or_else(dirs::cache_dir)
+            .map(|dir| dir.join("kk").join("log"))
+    });
+    let Some(log_dir) = log_dir.filter(|_| is_log_file) else {
+        return Ok(());
+    };
+
+    let level = match (&args.log_level, std::env::var(EnvFilter::DEFAULT_ENV)) {
+        (Some(level), _) => level.clone(),
+        (None, Ok(level)) => level,
+        (None, Err(_)) => config.log_level.clone().unwrap_or("info".to_string()),
+    };
+    let env_filter = EnvFilter::try_new(&level)
+        .map_err(|e| format!("invalid log level `{level}`: {e}"))?
+        .add_directive(format!("{}=off", access_log::TARGET).parse().unwrap());
+
+    let appender = |name: &str| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(name)
+            .build(&log_dir)
+            .map_err(|e| format!("can not write log to {}: {e}", log_dir.display()))
+    };
+    let file_appender = appender("my_app.log")?;
+    let access_appender = appender("access.log")?;
+
+    // 设置 tracing 订阅者，将日志输出到文件
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(file_appender)
+                .with_ansi(false)
+                .with_filter(env_filter),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_target(false)
+                .with_level(false)
+                .with_ansi(false)
+                .with_writer(access_appender)
+                .with_filter(filter_fn(|meta| meta.target() == access_log::TARGET)),
+        )
+        .init();
+    Ok(())
+}
+
+/// 阻塞直到收到 SIGINT 或 SIGTERM
+fn wait_for_signal() -> io::Result<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    rt.block_on(async {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut sigterm = signal(SignalKind::terminate())?;
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => result,
+                _ = sigterm.recv() => Ok(()),
+            }
+        }
+        #[cfg(not(unix))]
+        tokio::signal::ctrl_c().await
+    })
+}
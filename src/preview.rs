@@ -0,0 +1,82 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+    sync::LazyLock,
+};
+
+use syntect::{
+    highlighting::{Theme, ThemeSet},
+    html::highlighted_html_for_string,
+    parsing::SyntaxSet,
+};
+
+// 文本预览最多读这么多, 再大的文件直接下载看
+const TEXT_LIMIT: u64 = 512 * 1024;
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    ThemeSet::load_defaults()
+        .themes
+        .remove("InspiredGitHub")
+        .unwrap_or_default()
+});
+
+/// 网页上能直接预览的文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewKind {
+    Image,
+    Video,
+    Audio,
+    Pdf,
+    Text,
+}
+
+impl PreviewKind {
+    /// 按 MIME 判断, 源代码的 MIME 五花八门, 再看 syntect 认不认识扩展名
+    pub fn of(path: &Path, mime: &str) -> Option<Self> {
+        match mime.split_once('/') {
+            Some(("image", _)) => Some(Self::Image),
+            Some(("video", _)) => Some(Self::Video),
+            Some(("audio", _)) => Some(Self::Audio),
+            Some((_, "pdf")) => Some(Self::Pdf),
+            Some(("text", _)) | Some((_, "json" | "javascript" | "xml" | "toml" | "x-sh")) => {
+                Some(Self::Text)
+            }
+            _ => path
+                .extension()
+                .and_then(|ext| SYNTAX_SET.find_syntax_by_extension(&ext.to_string_lossy()))
+                .map(|_| Self::Text),
+        }
+    }
+
+    /// 模板里用的名字
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Pdf => "pdf",
+            Self::Text => "text",
+        }
+    }
+}
+
+/// 高亮后的 html, 文件太大时只有开头一部分, 第二个值表示是否截断
+pub fn highlight(path: &Path) -> io::Result<(String, bool)> {
+    let mut content = vec![];
+    std::fs::File::open(path)?
+        .take(TEXT_LIMIT + 1)
+        .read_to_end(&mut content)?;
+    let is_truncated = content.len() as u64 > TEXT_LIMIT;
+    content.truncate(TEXT_LIMIT as usize);
+    let text = String::from_utf8_lossy(&content);
+
+    let syntax = path
+        .extension()
+        .and_then(|ext| SYNTAX_SET.find_syntax_by_extension(&ext.to_string_lossy()))
+        .or_else(|| SYNTAX_SET.find_syntax_by_first_line(&text))
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    let html = highlighted_html_for_string(&text, &SYNTAX_SET, syntax, &THEME)
+        .map_err(io::Error::other)?;
+    Ok((html, is_truncated))
+}
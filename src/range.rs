@@ -0,0 +1,207 @@
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+};
+
+use axum::body::{Body, Bytes};
+use futures::{stream, StreamExt, TryStreamExt};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+// 请求的区间太多时不再按区间返回, 直接返回整个文件
+const MAX_RANGES: usize = 32;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// 没有 Range, 或者格式不对, 返回整个文件
+    Full,
+    /// 闭区间 [start, end]
+    Partial(Vec<(u64, u64)>),
+    /// 格式正确, 但没有一个区间在文件范围内
+    Unsatisfiable,
+}
+
+/// 解析 Range 头, 如 `bytes=0-99`, `bytes=100-`, `bytes=-100`, `bytes=0-1,5-9`
+pub fn parse(header: &str, len: u64) -> RangeRequest {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+
+    let mut ranges = vec![];
+    for part in spec.split(',') {
+        let Some((start, end)) = part.trim().split_once('-') else {
+            return RangeRequest::Full;
+        };
+        let range = match (start.trim(), end.trim()) {
+            // bytes=-n, 最后 n 个字节
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(0) => None,
+                Ok(n) if len > 0 => Some((len.saturating_sub(n), len - 1)),
+                Ok(_) => None,
+                Err(_) => return RangeRequest::Full,
+            },
+            (start, end) => {
+                let Ok(start) = start.parse::<u64>() else {
+                    return RangeRequest::Full;
+                };
+                let end = if end.is_empty() {
+                    u64::MAX
+                } else {
+                    match end.parse::<u64>() {
+                        Ok(end) if end >= start => end,
+                        _ => return RangeRequest::Full,
+                    }
+                };
+                (start < len).then(|| (start, end.min(len - 1)))
+            }
+        };
+        ranges.extend(range);
+    }
+
+    if ranges.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else if ranges.len() > MAX_RANGES {
+        RangeRequest::Full
+    } else {
+        RangeRequest::Partial(ranges)
+    }
+}
+
+/// 打开文件, 返回 [start, end] 这段内容
+pub async fn file_part(
+    path: &Path,
+    start: u64,
+    end: u64,
+) -> std::io::Result<ReaderStream<impl AsyncRead>> {
+    let mut file = File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    Ok(ReaderStream::new(file.take(end - start + 1)))
+}
+
+/// multipart/byteranges 的 body 和总长度
+pub fn multipart_body(
+    path: PathBuf,
+    ranges: Vec<(u64, u64)>,
+    len: u64,
+    content_type: &str,
+    boundary: &str,
+) -> (Body, u64) {
+    let heads = ranges
+        .iter()
+        .map(|(start, end)| {
+            format!(
+                "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: bytes {start}-{end}/{len}\r\n\r\n"
+            )
+        })
+        .collect::<Vec<_>>();
+    let tail = format!("\r\n--{boundary}--\r\n");
+
+    let content_length = heads.iter().map(|h| h.len() as u64).sum::<u64>()
+        + ranges
+            .iter()
+            .map(|(start, end)| end - start + 1)
+            .sum::<u64>()
+        + tail.len() as u64;
+
+    let parts = heads
+        .into_iter()
+        .zip(ranges)
+        .map(move |(head, (start, end))| {
+            let path = path.clone();
+            let head = stream::once(async move { Ok(Bytes::from(head)) });
+            let data =
+                stream::once(async move { file_part(&path, start, end).await }).try_flatten();
+            head.chain(data)
+        });
+    let tail = stream::once(async move { Ok::<_, std::io::Error>(Bytes::from(tail)) });
+
+    let body = Body::from_stream(stream::iter(parts).flatten().chain(tail));
+    (body, content_length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_range() {
+        assert_eq!(
+            parse("bytes=0-99", 1000),
+            RangeRequest::Partial(vec![(0, 99)])
+        );
+        // 结尾超出文件时截到最后一个字节
+        assert_eq!(
+            parse("bytes=900-2000", 1000),
+            RangeRequest::Partial(vec![(900, 999)])
+        );
+    }
+
+    #[test]
+    fn open_ended_range() {
+        assert_eq!(
+            parse("bytes=100-", 1000),
+            RangeRequest::Partial(vec![(100, 999)])
+        );
+        assert_eq!(
+            parse("bytes=00-", 1000),
+            RangeRequest::Partial(vec![(0, 999)])
+        );
+    }
+
+    #[test]
+    fn suffix_range() {
+        assert_eq!(
+            parse("bytes=-100", 1000),
+            RangeRequest::Partial(vec![(900, 999)])
+        );
+        assert_eq!(
+            parse("bytes=-2000", 1000),
+            RangeRequest::Partial(vec![(0, 999)])
+        );
+        assert_eq!(parse("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=-10", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn multi_range() {
+        assert_eq!(
+            parse("bytes=0-1, 5-9,-2", 100),
+            RangeRequest::Partial(vec![(0, 1), (5, 9), (98, 99)])
+        );
+        // 超出文件的区间丢掉, 剩下的照常返回
+        assert_eq!(
+            parse("bytes=0-1,200-300", 100),
+            RangeRequest::Partial(vec![(0, 1)])
+        );
+        let too_many = (0..=MAX_RANGES)
+            .map(|i| format!("{i}-{i}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(parse(&format!("bytes={too_many}"), 100), RangeRequest::Full);
+    }
+
+    #[test]
+    fn unsatisfiable_range() {
+        assert_eq!(parse("bytes=1000-", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=2000-3000", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=0-0", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn malformed_range() {
+        for header in [
+            "items=0-1",
+            "bytes=abc",
+            "bytes=a-1",
+            "bytes=0-b",
+            "bytes=9-5",
+            "bytes=0-1,x",
+            "bytes=--1",
+        ] {
+            assert_eq!(parse(header, 1000), RangeRequest::Full, "{header}");
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use axum::body::Bytes;
+use futures::{Stream, TryStreamExt};
+use tokio::fs::{File, OpenOptions};
+use tokio_util::io::StreamReader;
+
+/// 接收上传的目录, 以及已经接收完成的文件
+#[derive(Debug, Default)]
+pub struct ReceiveInfo {
+    pub dir: Option<PathBuf>,
+    pub files: Vec<PathBuf>,
+}
+
+/// 把 stream 写入 dir 下名为 name 的文件, 重名时自动改名, 返回最终路径
+pub async fn save_stream<S, E>(dir: &Path, name: &str, stream: S) -> io::Result<PathBuf>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    // 只取文件名, 防止 ../ 之类写到接收目录之外
+    let name = Path::new(name)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .filter(|n| !n.is_empty())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid file name"))?;
+
+    let (path, mut file) = create_unique(dir, &name).await?;
+    let mut reader = StreamReader::new(stream.map_err(io::Error::other));
+    if let Err(e) = tokio::io::copy(&mut reader, &mut file).await {
+        // 上传中断, 不留半个文件
+        drop(file);
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e);
+    }
+
+    Ok(path)
+}
+
+/// 创建 dir/name, 已存在则依次尝试 name(1), name(2) ...
+async fn create_unique(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (name, String::new()),
+    };
+
+    let mut n = 0;
+    loop {
+        let file_name = if n == 0 {
+            name.to_string()
+        } else {
+            format!("{stem}({n}){ext}")
+        };
+        let path = dir.join(file_name);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::random_string;
+
+// 分享 id 的长度
+const ID_LEN: usize = 12;
+
+// 过期时间的可选项, 单位分钟, 在 Shares 中按键循环切换
+const EXPIRE_MINUTES: [u64; 4] = [5, 15, 30, 60];
+// 最多下载次数的可选项
+const MAX_DOWNLOADS: [u32; 4] = [1, 3, 5, 10];
+
+// url 路径中的一段, 除了这几个字符都要编码
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// 一个分享, 网页上只通过 id 访问, 不暴露实际路径
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub id: String,
+    pub path: PathBuf,
+    pub expires_at: Option<SystemTime>,
+    pub max_downloads: Option<u32>,
+    pub downloads: u32,
+    // 是否保存到状态文件, 下次启动时恢复
+    #[serde(skip, default = "default_persist")]
+    pub persist: bool,
+}
+
+impl Share {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            id: random_string(ID_LEN),
+            path,
+            expires_at: None,
+            max_downloads: None,
+            downloads: 0,
+            persist: true,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|t| t <= SystemTime::now())
+    }
+
+    pub fn is_used_up(&self) -> bool {
+        self.max_downloads.is_some_and(|max| self.downloads >= max)
+    }
+
+    /// 剩余分钟数, 向上取整
+    pub fn minutes_left(&self) -> Option<u64> {
+        self.expires_at.map(|t| {
+            let left = t.duration_since(SystemTime::now()).unwrap_or_default();
+            left.as_secs().div_ceil(60)
+        })
+    }
+
+    /// 不过期 -> 5 -> 15 -> 30 -> 60 分钟 -> 不过期, 从现在开始算
+    pub fn cycle_expire(&mut self) {
+        let current = self.minutes_left().unwrap_or(0);
+        let next = match self.expires_at {
+            None => EXPIRE_MINUTES.first(),
+            Some(_) => EXPIRE_MINUTES.iter().find(|&&m| m > current),
+        };
+        self.expires_at = next.map(|m| SystemTime::now() + Duration::from_secs(m * 60));
+    }
+
+    /// 不限 -> 1 -> 3 -> 5 -> 10 次 -> 不限
+    pub fn cycle_max_downloads(&mut self) {
+        self.max_downloads = match self.max_downloads {
+            None => MAX_DOWNLOADS.first().copied(),
+            Some(max) => MAX_DOWNLOADS.iter().find(|&&m| m > max).copied(),
+        };
+    }
+
+    /// 限制的描述, 如 `12m 1/3`, 没有限制返回 None
+    pub fn limit_desc(&self) -> Option<String> {
+        let mut desc = vec![];
+        if let Some(minutes) = self.minutes_left() {
+            desc.push(format!("{minutes}m"));
+        }
+        if let Some(max) = self.max_downloads {
+            desc.push(format!("{}/{max}", self.downloads));
+        }
+        (!desc.is_empty()).then(|| desc.join(" "))
+    }
+
+    /// 网页上打开这个分享的路径, 文件直接下载, 目录进入浏览
+    pub fn link(&self) -> String {
+        let url = share_url(&self.id, &self.path, &self.path);
+        if self.path.is_dir() {
+            format!("/browse/{url}")
+        } else {
+            format!("/download/{url}")
+        }
+    }
+}
+
+fn default_persist() -> bool {
+    true
+}
+
+/// url 路径为 `id/相对路径`, 单个文件的分享为 `id/文件名`, 目录分享本身为 `id`
+pub fn share_url(id: &str, root: &Path, path: &Path) -> String {
+    let rel = if root.is_dir() {
+        path.strip_prefix(root).unwrap_or(Path::new(""))
+    } else {
+        Path::new(root.file_name().unwrap_or_default())
+    };
+    let mut url = id.to_string();
+    for c in rel.components() {
+        url.push('/');
+        url.extend(utf8_percent_encode(
+            &c.as_os_str().to_string_lossy(),
+            PATH_SEGMENT,
+        ));
+    }
+    url
+}
+
+/// 记一次下载, 分享不存在, 已过期或者次数已用完返回 false.
+/// 用完的分享先留着, 已经开始的下载还能断点续传, 下载完成后由 remove_used_up 移除
+pub fn take_download(share_arr: &mut [Share], id: &str) -> bool {
+    match share_arr
+        .iter_mut()
+        .find(|s| s.id == id && !s.is_expired() && !s.is_used_up())
+    {
+        Some(share) => {
+            share.downloads += 1;
+            true
+        }
+        None => false,
+    }
+}
+
+/// 移除次数已用完的分享, 有移除返回 true
+pub fn remove_used_up(share_arr: &mut Vec<Share>, id: &str) -> bool {
+    let len = share_arr.len();
+    share_arr.retain(|s| s.id != id || !s.is_used_up());
+    share_arr.len() != len
+}
+
+/// 打包下载时一次记多个分享, 有一个不能下载就一个都不记, 返回 false
+pub fn take_downloads(share_arr: &mut [Share], id_arr: &[String]) -> bool {
+    let is_all_ok = id_arr.iter().all(|id| {
+        share_arr
+            .iter()
+            .any(|s| &s.id == id && !s.is_expired() && !s.is_used_up())
+    });
+    if is_all_ok {
+        for id in id_arr {
+            take_download(share_arr, id);
+        }
+    }
+    is_all_ok
+}
+
+/// 移除已过期的分享, 有移除返回 true
+pub fn remove_expired(share_arr: &mut Vec<Share>) -> bool {
+    let len = share_arr.len();
+    share_arr.retain(|s| !s.is_expired());
+    share_arr.len() != len
+}
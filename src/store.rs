@@ -0,0 +1,61 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crate::share::Share;
+
+// 状态文件名, 放在 `dirs::data_dir()/kk` 下
+const SHARES_FILE: &str = "shares.json";
+
+fn shares_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("kk").join(SHARES_FILE))
+}
+
+/// 读取上次保存的分享, 去掉文件已经不存在, 已过期或者次数已用完的
+pub fn load_shares() -> Vec<Share> {
+    let Some(path) = shares_file() else {
+        return vec![];
+    };
+    let share_arr: Vec<Share> = match fs::read(&path) {
+        Ok(content) => match serde_json::from_slice(&content) {
+            Ok(share_arr) => share_arr,
+            Err(e) => {
+                tracing::warn!("can not parse {}: {e}", path.display());
+                return vec![];
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            tracing::warn!("can not read {}: {e}", path.display());
+            return vec![];
+        }
+    };
+    share_arr
+        .into_iter()
+        .filter(|s| s.path.exists() && !s.is_expired() && !s.is_used_up())
+        .collect()
+}
+
+/// 保存需要持久化的分享, 先写临时文件再改名, 中途退出不会写坏
+pub fn save_shares(share_arr: &[Share]) {
+    let Some(path) = shares_file() else {
+        return;
+    };
+    let share_arr: Vec<&Share> = share_arr.iter().filter(|s| s.persist).collect();
+    if let Err(e) = write_atomic(&path, &share_arr) {
+        tracing::warn!("can not save {}: {e}", path.display());
+    }
+}
+
+fn write_atomic(path: &PathBuf, share_arr: &[&Share]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(&serde_json::to_vec_pretty(share_arr)?)?;
+    file.sync_all()?;
+    fs::rename(tmp, path)
+}
@@ -0,0 +1,88 @@
+use std::{
+    fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use image::{codecs::jpeg::JpegEncoder, ImageFormat};
+use sha2::{Digest, Sha256};
+
+use crate::utils::random_string;
+
+// 缩略图最长边, 卡片上显示足够清楚
+const THUMB_SIZE: u32 = 320;
+const JPEG_QUALITY: u8 = 80;
+
+/// 能解码的图片格式才生成缩略图
+pub fn is_supported(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| format.reading_enabled())
+}
+
+/// JPEG 格式的缩略图, 放在 `<cache dir>/kk/thumb` 下, 原图改了会重新生成
+pub fn thumbnail(path: &Path) -> io::Result<Vec<u8>> {
+    let cache_path = cache_path(path)?;
+    if let Some(cache_path) = &cache_path {
+        if let Ok(content) = fs::read(cache_path) {
+            return Ok(content);
+        }
+    }
+
+    let img = image::open(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    // JPEG 没有透明通道
+    let thumb = img.thumbnail(THUMB_SIZE, THUMB_SIZE).into_rgb8();
+    let mut content = vec![];
+    thumb
+        .write_with_encoder(JpegEncoder::new_with_quality(
+            Cursor::new(&mut content),
+            JPEG_QUALITY,
+        ))
+        .map_err(io::Error::other)?;
+
+    // 缓存写不了不影响这次返回
+    if let Some(cache_path) = &cache_path {
+        if let Err(e) = write_cache(cache_path, &content) {
+            tracing::warn!("can not write {}: {e}", cache_path.display());
+        }
+    }
+    Ok(content)
+}
+
+/// 由路径, 大小和修改时间算出缓存文件名
+fn cache_path(path: &Path) -> io::Result<Option<PathBuf>> {
+    let Some(dir) = dirs::cache_dir() else {
+        return Ok(None);
+    };
+    let metadata = path.metadata()?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_os_str().as_encoded_bytes());
+    hasher.update(metadata.len().to_le_bytes());
+    hasher.update(mtime.to_le_bytes());
+    let name = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    Ok(Some(
+        dir.join("kk")
+            .join("thumb")
+            .join(format!("{name}-{THUMB_SIZE}.jpg")),
+    ))
+}
+
+/// 先写临时文件再改名, 同时请求同一张图也不会读到一半
+fn write_cache(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension(format!("{}.tmp", random_string(8)));
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
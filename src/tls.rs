@@ -0,0 +1,119 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    ServerConfig,
+};
+use sha2::{Digest, Sha256};
+
+// 自动生成的自签名证书, 放在 `<config dir>/kk` 下
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// 加载好的证书, 指纹显示在 TUI 里, 对方可以在浏览器里核对
+pub struct Tls {
+    pub config: Arc<ServerConfig>,
+    /// 证书的 SHA-256, `AB:CD:...`
+    pub fingerprint: String,
+}
+
+impl Tls {
+    /// 用给定的证书和私钥, 没有给定时用 (第一次时生成) 自签名证书, hosts 写进证书的 SAN
+    pub fn load(cert_key: Option<(&Path, &Path)>, hosts: Vec<String>) -> io::Result<Self> {
+        let (cert_path, key_path) = match cert_key {
+            Some((cert, key)) => (cert.to_path_buf(), key.to_path_buf()),
+            None => self_signed(hosts)?,
+        };
+        let cert_arr = read_certs(&cert_path)?;
+        let key = read_key(&key_path)?;
+        let fingerprint = cert_arr
+            .first()
+            .map(|cert| fingerprint(cert))
+            .unwrap_or_default();
+
+        let mut config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .and_then(|builder| {
+                    builder
+                        .with_no_client_auth()
+                        .with_single_cert(cert_arr, key)
+                })
+                .map_err(|e| invalid_data(&cert_path, e))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(Self {
+            config: Arc::new(config),
+            fingerprint,
+        })
+    }
+}
+
+/// 已经生成过就直接用, 换了网络 ip 变了也不重新生成, 否则指纹每次都不一样
+fn self_signed(hosts: Vec<String>) -> io::Result<(PathBuf, PathBuf)> {
+    let dir = dirs::config_dir()
+        .map(|dir| dir.join("kk"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config dir"))?;
+    let cert_path = dir.join(CERT_FILE);
+    let key_path = dir.join(KEY_FILE);
+    if cert_path.exists() && key_path.exists() {
+        return Ok((cert_path, key_path));
+    }
+
+    let certified = rcgen::generate_simple_self_signed(hosts).map_err(io::Error::other)?;
+    fs::create_dir_all(&dir)?;
+    fs::write(&cert_path, certified.cert.pem())?;
+    write_private(&key_path, certified.signing_key.serialize_pem().as_bytes())?;
+    tracing::info!("generate self-signed certificate {}", cert_path.display());
+    Ok((cert_path, key_path))
+}
+
+/// 私钥只有自己能读
+fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    io::Write::write_all(&mut options.open(path)?, content)
+}
+
+fn read_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let content = fs::read(path).map_err(|e| with_path(path, e))?;
+    let cert_arr = rustls_pemfile::certs(&mut content.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| with_path(path, e))?;
+    if cert_arr.is_empty() {
+        return Err(invalid_data(path, "no certificate"));
+    }
+    Ok(cert_arr)
+}
+
+fn read_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let content = fs::read(path).map_err(|e| with_path(path, e))?;
+    rustls_pemfile::private_key(&mut content.as_slice())
+        .map_err(|e| with_path(path, e))?
+        .ok_or_else(|| invalid_data(path, "no private key"))
+}
+
+fn fingerprint(cert: &CertificateDer) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn with_path(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {e}", path.display()))
+}
+
+fn invalid_data(path: &Path, e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {e}", path.display()),
+    )
+}
@@ -0,0 +1,136 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio_util::sync::CancellationToken;
+
+// 结束的传输最多保留几条, 方便确认对方是否下载完
+const HISTORY_LEN: usize = 5;
+// 超过这么久没有发出数据算卡住
+const STALL_AFTER: Duration = Duration::from_secs(5);
+
+/// 一次下载, 发送的字节数由 web 那边更新, TUI 读取
+#[derive(Debug)]
+pub struct Transfer {
+    pub name: String,
+    pub client: IpAddr,
+    /// 打包下载时不知道总大小
+    pub total: Option<u64>,
+    started: Instant,
+    sent: AtomicU64,
+    // 最后一次发出数据时距开始的毫秒数
+    last_active_ms: AtomicU64,
+    // 用 token 表示结束, 可以等它
+    finished: CancellationToken,
+    // TUI 里取消下载
+    cancel: CancellationToken,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferState {
+    Running,
+    Stalled,
+    Done,
+    /// 没有发完连接就断了
+    Aborted,
+    Canceled,
+}
+
+impl Transfer {
+    pub fn new(name: String, client: IpAddr, total: Option<u64>) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            client,
+            total,
+            started: Instant::now(),
+            sent: AtomicU64::new(0),
+            last_active_ms: AtomicU64::new(0),
+            finished: CancellationToken::new(),
+            cancel: CancellationToken::new(),
+        })
+    }
+
+    /// 中断下载, 连接会被断开
+    pub fn cancel(&self) {
+        if !self.is_finished() {
+            self.cancel.cancel();
+        }
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// 从开始到现在 (或者到结束) 的平均速度, 字节每秒
+    pub fn speed(&self) -> u64 {
+        let ms = self.last_active_ms.load(Ordering::Relaxed).max(1);
+        self.sent() * 1000 / ms
+    }
+
+    pub fn ratio(&self) -> Option<f64> {
+        self.total
+            .map(|total| (self.sent() as f64 / total.max(1) as f64).min(1.0))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_cancelled()
+    }
+
+    pub fn state(&self) -> TransferState {
+        let is_complete = self.total.is_none_or(|total| self.sent() >= total);
+        if self.is_finished() {
+            if is_complete {
+                TransferState::Done
+            } else if self.cancel.is_cancelled() {
+                TransferState::Canceled
+            } else {
+                TransferState::Aborted
+            }
+        } else if self.started.elapsed().as_millis() as u64
+            > self.last_active_ms.load(Ordering::Relaxed) + STALL_AFTER.as_millis() as u64
+        {
+            TransferState::Stalled
+        } else {
+            TransferState::Running
+        }
+    }
+
+    /// 取消时断开连接用
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub fn add_sent(&self, len: usize) {
+        self.sent.fetch_add(len as u64, Ordering::Relaxed);
+        self.last_active_ms
+            .store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// body 被丢弃时 (发完或者连接断开) 调用
+    pub fn finish(&self) {
+        self.finished.cancel();
+    }
+
+    /// 等到 body 被丢弃
+    pub async fn wait_finished(&self) {
+        self.finished.cancelled().await;
+    }
+}
+
+/// 去掉较早结束的传输, 结束的只保留最近几条
+pub fn retain_recent(transfer_arr: &mut Vec<Arc<Transfer>>) {
+    let finished = transfer_arr.iter().filter(|t| t.is_finished()).count();
+    let mut to_remove = finished.saturating_sub(HISTORY_LEN);
+    transfer_arr.retain(|t| {
+        let is_remove = to_remove > 0 && t.is_finished();
+        if is_remove {
+            to_remove -= 1;
+        }
+        !is_remove
+    });
+}
@@ -1,15 +1,48 @@
-use std::{cmp::Ordering, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+};
+
+use rand::{distributions::Alphanumeric, Rng};
+
+/// 目录在前, 文件在后, 同类按文件名排序
+pub fn cmp_path(a: &Path, b: &Path) -> Ordering {
+    if a.is_dir() && b.is_file() {
+        Ordering::Less
+    } else if a.is_file() && b.is_dir() {
+        Ordering::Greater
+    } else if let (Some(a_name), Some(b_name)) = (a.file_name(), b.file_name()) {
+        a_name.cmp(b_name)
+    } else {
+        Ordering::Equal
+    }
+}
 
 pub fn sort_files(files: &mut [PathBuf]) {
-    files.sort_by(|a, b| {
-        if a.is_dir() && b.is_file() {
-            Ordering::Less
-        } else if a.is_file() && b.is_dir() {
-            Ordering::Greater
-        } else if let (Some(a_name), Some(b_name)) = (a.file_name(), b.file_name()) {
-            a_name.cmp(b_name)
-        } else {
-            Ordering::Equal
-        }
-    });
+    files.sort_by(|a, b| cmp_path(a, b));
+}
+
+/// 随机字母数字串, 用于分享 id, session token 等
+pub fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// 人看的大小, 如 `1.5 MB`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size} {}", UNITS[unit])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
 }
@@ -1,6 +1,11 @@
 use std::{
+    collections::HashSet,
+    future::IntoFuture,
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use askama::Template;
@@ -9,36 +14,86 @@ use axum::{
     body::Body,
     extract::{
         ws::{Message, WebSocket},
-        Query, State, WebSocketUpgrade,
+        ConnectInfo, Path as UrlPath, Query, State, WebSocketUpgrade,
     },
-    routing::get,
-    Router,
+    extract::{DefaultBodyLimit, FromRequest, Multipart, Request},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{Redirect, Response},
+    routing::{get, post},
+    Form, Router,
 };
+use axum_extra::extract::Query as MultiQuery;
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::{DateTime, Local};
 use futures::{SinkExt, StreamExt};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use serde::Deserialize;
 use tokio::{
     fs::File,
     runtime::Runtime,
     sync::{
-        broadcast::{self},
+        broadcast::{self, error::RecvError},
         mpsc::Receiver,
         oneshot, RwLock,
     },
 };
 use tokio_util::io::ReaderStream;
+use walkdir::WalkDir;
 
-use crate::consts::PORT;
+use crate::{
+    access_log::{self, AccessEntry, ShareName},
+    archive, assets,
+    auth::{Auth, Check, Throttle},
+    event::AppEvent,
+    ip_filter::IpFilter,
+    preview::{self, PreviewKind},
+    range::{self, RangeRequest},
+    receive::{self, ReceiveInfo},
+    share::{self, share_url, Share},
+    store, thumb,
+    transfer::{Transfer, TransferState},
+    utils::format_size,
+};
+
+// 退出时最多等进行中的下载这么久, 之后直接断开
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone)]
 struct AppState {
-    share_path_arr: Arc<RwLock<Vec<PathBuf>>>,
+    share_arr: Arc<RwLock<Vec<Share>>>,
+    receive_info: Arc<RwLock<ReceiveInfo>>,
+    auth: Arc<RwLock<Option<Auth>>>,
     broadcast_tx: broadcast::Sender<()>,
+    // 通知 TUI 重绘, headless 时没有接收方
+    event_tx: std::sync::mpsc::Sender<AppEvent>,
+    ip_filter: Arc<IpFilter>,
+    throttle: Arc<Throttle>,
+    // 已经计过下载次数的 (分享 id, 客户端, ETag), 之后的断点续传不再计
+    counted: Arc<Mutex<HashSet<(String, IpAddr, String)>>>,
+    // HTTPS 时 cookie 加上 Secure
+    is_tls: bool,
 }
 impl AppState {
-    fn new(share_path_arr: Arc<RwLock<Vec<PathBuf>>>, broadcast_tx: broadcast::Sender<()>) -> Self {
+    fn new(
+        share_arr: Arc<RwLock<Vec<Share>>>,
+        receive_info: Arc<RwLock<ReceiveInfo>>,
+        auth: Arc<RwLock<Option<Auth>>>,
+        broadcast_tx: broadcast::Sender<()>,
+        event_tx: std::sync::mpsc::Sender<AppEvent>,
+        ip_filter: IpFilter,
+        is_tls: bool,
+    ) -> Self {
         Self {
-            share_path_arr,
+            share_arr,
+            receive_info,
+            auth,
             broadcast_tx,
+            event_tx,
+            ip_filter: Arc::new(ip_filter),
+            throttle: Arc::new(Throttle::default()),
+            counted: Arc::default(),
+            is_tls,
         }
     }
 }
@@ -47,23 +102,163 @@ impl AppState {
 #[template(path = "index.html")]
 pub struct IndexTemplate;
 
+#[derive(Template)]
+#[template(path = "login.html")]
+pub struct LoginTemplate {
+    pub next: String,
+    pub is_wrong_pin: bool,
+}
+
 #[derive(Template)]
 #[template(path = "file_list.html")]
 pub struct FileListTemplate {
     pub file_arr: Vec<FileInfo>,
     pub is_hx_swap_oob: bool,
+    pub can_upload: bool,
+}
+
+#[derive(Template)]
+#[template(path = "dir.html")]
+pub struct DirTemplate {
+    pub name: String,
+    pub url: String,
+    pub parent: Option<String>,
+    pub file_arr: Vec<FileInfo>,
+}
+
+/// 预览弹窗里的内容, 由 htmx 换进去
+#[derive(Template)]
+#[template(path = "preview.html")]
+pub struct PreviewTemplate {
+    pub name: String,
+    pub url: String,
+    pub kind: &'static str,
+    // 文本预览高亮后的 html
+    pub html: String,
+    pub is_truncated: bool,
 }
 
 pub struct FileInfo {
     name: String,
-    path: String,
+    // 分享 id 加上分享内的相对路径, 已经 url 编码, 如 `abc/sub/a.txt`
+    url: String,
+    is_dir: bool,
+    // 分享的过期时间, 下载次数限制
+    limit: Option<String>,
+    // 目录不统计大小, 为 0
+    size: u64,
+    size_text: Option<String>,
+    // unix 秒, 网页上排序用
+    mtime: i64,
+    mtime_text: String,
+    mime: String,
+    icon: &'static str,
+    // 能预览时是预览的类型, 见 PreviewKind::as_str
+    preview: Option<&'static str>,
+    has_thumb: bool,
+}
+
+impl FileInfo {
+    fn new(path: &Path, url: String) -> Self {
+        let is_dir = path.is_dir();
+        let metadata = path.metadata().ok();
+        let size = metadata
+            .as_ref()
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let mtime = metadata
+            .and_then(|m| m.modified().ok())
+            .map(DateTime::<Local>::from);
+        let mime = if is_dir {
+            "inode/directory".to_string()
+        } else {
+            mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .essence_str()
+                .to_string()
+        };
+        Self {
+            name: file_name(path),
+            url,
+            is_dir,
+            limit: None,
+            size,
+            size_text: (!is_dir).then(|| format_size(size)),
+            mtime: mtime.map(|t| t.timestamp()).unwrap_or(0),
+            mtime_text: mtime
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default(),
+            icon: icon_of(is_dir, &mime),
+            preview: PreviewKind::of(path, &mime)
+                .filter(|_| !is_dir)
+                .map(|kind| kind.as_str()),
+            has_thumb: !is_dir && thumb::is_supported(path),
+            mime,
+        }
+    }
+}
+
+/// 卡片标题前的图标, 按 MIME 大类区分
+fn icon_of(is_dir: bool, mime: &str) -> &'static str {
+    if is_dir {
+        return "📁";
+    }
+    match mime.split_once('/') {
+        Some(("image", _)) => "🖼️",
+        Some(("video", _)) => "🎬",
+        Some(("audio", _)) => "🎵",
+        Some(("text", _)) => "📄",
+        Some((_, "pdf")) => "📕",
+        Some((
+            _,
+            "zip" | "gzip" | "x-tar" | "x-7z-compressed" | "vnd.rar" | "x-rar-compressed",
+        )) => "📦",
+        _ => "📎",
+    }
+}
+
+/// 分享内的一个路径
+struct SharePath {
+    id: String,
+    // 分享本身的路径, 已 canonicalize
+    root: PathBuf,
+    // 已 canonicalize
+    path: PathBuf,
+}
+
+impl SharePath {
+    /// root 之下 path 对应的 url 路径
+    fn url_of(&self, path: &Path) -> String {
+        share_url(&self.id, &self.root, path)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|os_str| os_str.to_string_lossy().into_owned())
+        .unwrap_or("".to_string())
+}
+
+/// 监听和访问控制, 在 main 里准备好, 出错可以在进入 TUI 前提示
+pub struct ServeOptions {
+    pub listener: std::net::TcpListener,
+    pub ip_filter: IpFilter,
+    /// 有证书时用 HTTPS
+    pub tls: Option<Arc<rustls::ServerConfig>>,
+    /// 分享有变化时写状态文件
+    pub is_save: bool,
 }
 
 pub fn run(
+    options: ServeOptions,
     mut rx: Receiver<()>,
-    share_path_arr: Arc<RwLock<Vec<PathBuf>>>,
+    share_arr: Arc<RwLock<Vec<Share>>>,
+    receive_info: Arc<RwLock<ReceiveInfo>>,
+    auth: Arc<RwLock<Option<Auth>>>,
+    event_tx: std::sync::mpsc::Sender<AppEvent>,
     shutdown_rx: oneshot::Receiver<()>,
-) {
+) -> JoinHandle<()> {
     std::thread::spawn(move || {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
@@ -77,25 +272,279 @@ pub fn run(
                 }
             });
 
-            let app_state = AppState::new(share_path_arr, broadcast_tx);
+            // 定时移除过期的分享
+            let tx_clone = broadcast_tx.clone();
+            let share_arr_clone = share_arr.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    if !share_arr_clone.read().await.iter().any(Share::is_expired) {
+                        continue;
+                    }
+                    if share::remove_expired(&mut *share_arr_clone.write().await) {
+                        let _ = tx_clone.send(());
+                    }
+                }
+            });
+
+            // 分享有变化就通知 TUI
+            let mut change_rx = broadcast_tx.subscribe();
+            let event_tx_clone = event_tx.clone();
+            tokio::spawn(async move {
+                while !matches!(change_rx.recv().await, Err(RecvError::Closed)) {
+                    let _ = event_tx_clone.send(AppEvent::Changed);
+                }
+            });
+
+            // 分享有变化就保存
+            let mut change_rx = broadcast_tx.subscribe();
+            let share_arr_clone = share_arr.clone();
+            let is_save = options.is_save;
+            tokio::spawn(async move {
+                if !is_save {
+                    return;
+                }
+                while !matches!(change_rx.recv().await, Err(RecvError::Closed)) {
+                    let share_arr = share_arr_clone.read().await.clone();
+                    let _ =
+                        tokio::task::spawn_blocking(move || store::save_shares(&share_arr)).await;
+                }
+            });
+
+            let app_state = AppState::new(
+                share_arr,
+                receive_info,
+                auth,
+                broadcast_tx,
+                event_tx,
+                options.ip_filter,
+                options.tls.is_some(),
+            );
             let app = Router::new()
                 .route("/", get(index))
-                .route("/download", get(download))
+                .route("/browse/:id", get(browse))
+                .route("/browse/:id/*path", get(browse))
+                .route("/download/:id/*path", get(download))
+                .route("/view/:id/*path", get(view))
+                .route("/preview/:id/*path", get(preview))
+                .route("/thumb/:id/*path", get(thumbnail))
+                .route("/download.zip/:id", get(download_zip))
+                .route("/download.zip/:id/*path", get(download_zip))
+                .route("/download.tar", get(download_tar))
+                .route("/download.tar.gz", get(download_tar_gz))
+                .route("/upload", post(upload).layer(DefaultBodyLimit::disable()))
                 .route("/websocket", get(websocket_handler))
+                // 以上路由都需要 PIN
+                .layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    require_pin,
+                ))
+                .route("/login", get(login_page).post(login))
+                .route("/static/:file", get(assets::static_file))
+                .layer(middleware::from_fn_with_state(app_state.clone(), check_ip))
+                // 被拒绝的请求也记到访问日志
+                .layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    access_log,
+                ))
                 .with_state(app_state);
 
-            let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", PORT))
-                .await
-                .unwrap();
-            tracing::debug!("listening on {}", listener.local_addr().unwrap());
-            axum::serve(listener, app)
-                .with_graceful_shutdown(async {
-                    shutdown_rx.await.ok();
-                })
-                .await
-                .unwrap();
+            let service = app.into_make_service_with_connect_info::<SocketAddr>();
+            tracing::debug!("listening on {}", options.listener.local_addr().unwrap());
+            match options.tls {
+                Some(tls) => {
+                    let handle = axum_server::Handle::new();
+                    let handle_clone = handle.clone();
+                    tokio::spawn(async move {
+                        shutdown_rx.await.ok();
+                        handle_clone.graceful_shutdown(Some(SHUTDOWN_GRACE));
+                    });
+                    axum_server::from_tcp_rustls(options.listener, RustlsConfig::from_config(tls))
+                        .handle(handle)
+                        .serve(service)
+                        .await
+                        .unwrap();
+                }
+                None => {
+                    let listener = tokio::net::TcpListener::from_std(options.listener).unwrap();
+                    let (grace_tx, grace_rx) = oneshot::channel::<()>();
+                    let server = axum::serve(listener, service).with_graceful_shutdown(async {
+                        shutdown_rx.await.ok();
+                        let _ = grace_tx.send(());
+                    });
+                    tokio::select! {
+                        result = server.into_future() => result.unwrap(),
+                        _ = async {
+                            grace_rx.await.ok();
+                            tokio::time::sleep(SHUTDOWN_GRACE).await;
+                        } => tracing::warn!("shutdown timeout, drop running downloads"),
+                    }
+                }
+            }
         });
-    });
+        // 不等还在打包的阻塞任务
+        rt.shutdown_background();
+    })
+}
+
+/// 每个请求记一条访问日志, 同时显示在 TUI 的 Log 里
+async fn access_log(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let entry = AccessEntry::new(client.ip(), &request);
+    let response = next.run(request).await;
+    let event_tx = state.event_tx.clone();
+    access_log::log_body(response, entry, move |entry| {
+        let _ = event_tx.send(AppEvent::Access(entry));
+    })
+}
+
+/// 不在允许的网段, 或者在拒绝的网段里, 返回 403
+async fn check_ip(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.ip_filter.is_allowed(client.ip()) {
+        return next.run(request).await;
+    }
+    tracing::warn!("reject {} {}", client.ip(), request.uri().path());
+    (StatusCode::FORBIDDEN, "Forbidden").into_response()
+}
+
+/// 设置了 PIN 时, 没有通过验证的浏览器跳转到登录页, 其它客户端返回 401
+async fn require_pin(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let check = match state.auth.read().await.as_ref() {
+        Some(auth) => auth.check(request.headers()),
+        None => Check::Session,
+    };
+    // 带了 PIN 的记一次尝试, 锁定中 PIN 对了也不放行
+    if matches!(check, Check::RightPin | Check::WrongPin) {
+        if let Err(wait) = state.throttle.attempt(client.ip()) {
+            return too_many_attempts(client.ip(), wait);
+        }
+    }
+    match check {
+        Check::Session => return next.run(request).await,
+        Check::RightPin => {
+            state.throttle.reset(client.ip());
+            return next.run(request).await;
+        }
+        Check::Missing | Check::WrongPin => {}
+    }
+
+    let is_browser = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html"));
+    if is_browser {
+        // HTTP/2 的 uri 带着 scheme 和 host, 只要路径
+        let path = request.uri().path_and_query().map_or("/", |p| p.as_str());
+        let next = utf8_percent_encode(path, NON_ALPHANUMERIC).to_string();
+        Redirect::to(&format!("/login?next={next}")).into_response()
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"kk\"")],
+            "PIN required",
+        )
+            .into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct LoginParam {
+    #[serde(default)]
+    next: String,
+}
+
+async fn login_page(Query(p): Query<LoginParam>) -> impl IntoResponse {
+    LoginTemplate {
+        next: safe_next(&p.next).to_string(),
+        is_wrong_pin: false,
+    }
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    pin: String,
+    #[serde(default)]
+    next: String,
+}
+
+async fn login(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Form(form): Form<LoginForm>,
+) -> impl IntoResponse {
+    let next = safe_next(&form.next).to_string();
+    let set_cookie = match state.auth.read().await.as_ref() {
+        Some(auth) => auth
+            .is_pin(&form.pin)
+            .then(|| auth.set_cookie(state.is_tls)),
+        None => return Redirect::to(&next).into_response(),
+    };
+    // 锁定中 PIN 对了也不放行
+    if let Err(wait) = state.throttle.attempt(client.ip()) {
+        return too_many_attempts(client.ip(), wait);
+    }
+
+    match set_cookie {
+        Some(set_cookie) => {
+            state.throttle.reset(client.ip());
+            ([(header::SET_COOKIE, set_cookie)], Redirect::to(&next)).into_response()
+        }
+        None => {
+            // 拖慢暴力猜 PIN
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            (
+                StatusCode::UNAUTHORIZED,
+                LoginTemplate {
+                    next,
+                    is_wrong_pin: true,
+                },
+            )
+                .into_response()
+        }
+    }
+}
+
+fn too_many_attempts(client: IpAddr, wait: Duration) -> Response {
+    tracing::warn!(
+        "too many wrong PIN from {client}, locked for {}s",
+        wait.as_secs()
+    );
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, (wait.as_secs() + 1).to_string())],
+        "Too many wrong PIN, try again later",
+    )
+        .into_response()
+}
+
+/// 登录后只允许跳回本站路径, 浏览器会把 `\` 当成 `/`, `/\evil.com` 也是外站
+fn safe_next(next: &str) -> &str {
+    let is_path = next.starts_with('/')
+        && !next.starts_with("//")
+        && !next.contains('\\')
+        && !next.chars().any(char::is_control);
+    if is_path {
+        next
+    } else {
+        "/"
+    }
 }
 
 async fn index() -> impl IntoResponse {
@@ -130,8 +579,9 @@ async fn websocket(stream: WebSocket, state: AppState) {
 
 async fn get_list_string(state: AppState) -> String {
     let list = FileListTemplate {
-        file_arr: path_arr_2_file_arr(state.share_path_arr).await,
+        file_arr: share_arr_2_file_arr(state.share_arr).await,
         is_hx_swap_oob: true,
+        can_upload: state.receive_info.read().await.dir.is_some(),
     };
     match list.render() {
         Ok(l) => l,
@@ -143,60 +593,833 @@ async fn get_list_string(state: AppState) -> String {
 }
 
 #[derive(Deserialize)]
-struct DownloadParam {
+struct ShareParam {
+    id: String,
+    #[serde(default)]
     path: String,
 }
 
+async fn browse(
+    UrlPath(p): UrlPath<ShareParam>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let Some(share_path) = resolve(&state.share_arr, &p.id, &p.path).await else {
+        tracing::error!("Error browse dir, dir isn't share");
+        return (StatusCode::NOT_FOUND, "Dir isn't share").into_response();
+    };
+    let dir = &share_path.path;
+    if !dir.is_dir() {
+        return (StatusCode::BAD_REQUEST, "Not a dir").into_response();
+    }
+
+    let file_arr = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(1)
+        .sort_by(|a, b| {
+            b.file_type()
+                .is_dir()
+                .cmp(&a.file_type().is_dir())
+                .then_with(|| a.file_name().cmp(b.file_name()))
+        })
+        .into_iter()
+        .flatten()
+        .map(|entry| FileInfo::new(entry.path(), share_path.url_of(entry.path())))
+        .collect::<Vec<_>>();
+
+    // 分享根目录不再向上
+    let parent = if *dir == share_path.root {
+        None
+    } else {
+        dir.parent().map(|p| share_path.url_of(p))
+    };
+
+    DirTemplate {
+        name: file_name(dir),
+        url: share_path.url_of(dir),
+        parent,
+        file_arr,
+    }
+    .into_response()
+}
+
 async fn download(
-    Query(p): Query<DownloadParam>,
+    UrlPath(p): UrlPath<ShareParam>,
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    send_file(p, state, client.ip(), &method, headers, false).await
+}
+
+/// 和下载一样, 但媒体和 PDF 带上 MIME 让浏览器直接显示, 视频音频可以拖动进度
+async fn view(
+    UrlPath(p): UrlPath<ShareParam>,
     State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let path_to_download = Path::new(&p.path);
-    let share_path_arr = state.share_path_arr.read().await;
-    if share_path_arr.iter().any(|p| p.eq(path_to_download)) {
+    send_file(p, state, client.ip(), &method, headers, true).await
+}
+
+async fn send_file(
+    p: ShareParam,
+    state: AppState,
+    client: IpAddr,
+    method: &Method,
+    headers: HeaderMap,
+    is_inline: bool,
+) -> Response {
+    // 已经计过次数的客户端, 分享用完了也能续传
+    let is_used_up_ok = state
+        .counted
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(id, ip, _)| *id == p.id && *ip == client);
+    let share_path = resolve_share(&state.share_arr, &p.id, &p.path, is_used_up_ok).await;
+    if let Some(share_path) = share_path {
+        if share_path.path.is_dir() {
+            return (StatusCode::BAD_REQUEST, "Not a file").into_response();
+        }
+        let file_range = match file_range(&share_path.path, &headers).await {
+            Ok(file_range) => file_range,
+            Err(e) => {
+                tracing::error!("Error streaming file: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to stream file")
+                    .into_response();
+            }
+        };
+        // 同一个客户端对同一个文件的断点续传不算一次新的下载, HEAD 不算下载
+        let key = (p.id.clone(), client, file_range.etag.clone());
+        let is_counted = state.counted.lock().unwrap().contains(&key);
+        let is_head = method == Method::HEAD;
+        let is_free = is_head || (is_counted && is_resume(&file_range.range));
+        if !is_free {
+            if !count_download(&state, &p.id, true).await {
+                return (StatusCode::GONE, "Share is used up").into_response();
+            }
+            // 顺便去掉已经移除的分享
+            let id_arr = state
+                .share_arr
+                .read()
+                .await
+                .iter()
+                .map(|s| s.id.clone())
+                .collect::<Vec<_>>();
+            let mut counted = state.counted.lock().unwrap();
+            counted.retain(|(id, _, _)| id_arr.contains(id));
+            counted.insert(key);
+        }
+        let is_to_end = match &file_range.range {
+            RangeRequest::Full => true,
+            RangeRequest::Partial(ranges) => {
+                ranges.iter().any(|(_, end)| *end + 1 == file_range.len)
+            }
+            RangeRequest::Unsatisfiable => false,
+        };
+        let name = file_name(&share_path.path);
+        let mime = mime_guess::from_path(&share_path.path).first_or_octet_stream();
+        // 只有媒体和 PDF 在浏览器里直接显示, 其它文件 (比如 html) 照样下载
+        let inline_kind = PreviewKind::of(&share_path.path, mime.essence_str())
+            .filter(|kind| is_inline && *kind != PreviewKind::Text);
+        let content_type = match inline_kind {
+            Some(_) => mime.to_string(),
+            None => "application/octet-stream".to_string(),
+        };
         // 调用上面定义的函数来处理下载
-        match stream_file(Path::new(&p.path)).await {
-            Ok(response_body) => response_body.into_response(),
+        match stream_file(&share_path.path, file_range, &content_type).await {
+            Ok(mut response) => {
+                let headers = response.headers_mut();
+                headers.insert(
+                    header::X_CONTENT_TYPE_OPTIONS,
+                    HeaderValue::from_static("nosniff"),
+                );
+                // svg 里的脚本不能以分享页面的身份运行, Chrome 不显示沙箱里的 PDF
+                if inline_kind.is_some_and(|kind| kind != PreviewKind::Pdf) {
+                    headers.insert(
+                        header::CONTENT_SECURITY_POLICY,
+                        HeaderValue::from_static("sandbox"),
+                    );
+                }
+                let disposition = match inline_kind {
+                    Some(_) => Some(inline(&name)),
+                    None => is_inline.then(|| attachment(&name)),
+                };
+                if let Some(value) = disposition.and_then(|v| v.parse().ok()) {
+                    headers.insert(header::CONTENT_DISPOSITION, value);
+                }
+                if is_head {
+                    return response;
+                }
+                let response = track(&state, response, name, client);
+                if is_to_end {
+                    if let Some(transfer) = response.extensions().get::<Arc<Transfer>>() {
+                        tokio::spawn(remove_when_done(state.clone(), p.id, transfer.clone()));
+                    }
+                }
+                response
+            }
             Err(e) => {
                 tracing::error!("Error streaming file: {}", e);
                 // 返回一个错误响应，实际应用中可能需要更详细的错误处理
-                (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to stream file",
-                )
-                    .into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to stream file").into_response()
             }
         }
     } else {
         tracing::error!("Error streaming file, file isn't share");
-        // 返回一个错误响应，实际应用中可能需要更详细的错误处理
         (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::NOT_FOUND,
             "Failed to stream file, file isn't share",
         )
             .into_response()
     }
 }
 
-async fn stream_file(path: &Path) -> Result<impl IntoResponse, std::io::Error> {
-    let file = File::open(path).await?;
-    let stream = ReaderStream::new(tokio::io::BufReader::new(file));
-    Ok(Body::from_stream(stream))
+/// 预览弹窗的内容, 媒体文件由 `/view` 加载, 文本在这里高亮好
+async fn preview(
+    UrlPath(p): UrlPath<ShareParam>,
+    State(state): State<AppState>,
+    method: Method,
+) -> impl IntoResponse {
+    let Some(share_path) = resolve(&state.share_arr, &p.id, &p.path).await else {
+        return (StatusCode::NOT_FOUND, "File isn't share").into_response();
+    };
+    let url = share_path.url_of(&share_path.path);
+    let path = share_path.path;
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let Some(kind) = PreviewKind::of(&path, mime.essence_str()).filter(|_| path.is_file()) else {
+        return (StatusCode::BAD_REQUEST, "Can not preview").into_response();
+    };
+
+    let (html, is_truncated) = if kind == PreviewKind::Text {
+        // 文本内容直接发出去了, 和下载一样计数, HEAD 除外
+        if method != Method::HEAD && !count_download(&state, &p.id, false).await {
+            return (StatusCode::GONE, "Share is used up").into_response();
+        }
+        let highlight_path = path.clone();
+        let result = tokio::task::spawn_blocking(move || preview::highlight(&highlight_path))
+            .await
+            .map_err(std::io::Error::other)
+            .and_then(|result| result);
+        match result {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::error!("Error highlight {}: {e}", path.display());
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file").into_response();
+            }
+        }
+    } else {
+        (String::new(), false)
+    };
+
+    PreviewTemplate {
+        name: file_name(&path),
+        url,
+        kind: kind.as_str(),
+        html,
+        is_truncated,
+    }
+    .into_response()
 }
 
-async fn path_arr_2_file_arr(path_arr: Arc<RwLock<Vec<PathBuf>>>) -> Vec<FileInfo> {
-    let path_arr = path_arr.read().await;
-    path_arr
-        .iter()
-        .map(|p| {
-            let name = p
-                .file_name()
-                .map(|os_str| os_str.to_string_lossy().into_owned())
-                .unwrap_or("".to_string());
-            FileInfo {
-                name,
-                path: p.to_string_lossy().to_string(),
+async fn download_zip(
+    UrlPath(p): UrlPath<ShareParam>,
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    method: Method,
+) -> impl IntoResponse {
+    let Some(share_path) = resolve(&state.share_arr, &p.id, &p.path).await else {
+        tracing::error!("Error zip dir, dir isn't share");
+        return (StatusCode::NOT_FOUND, "Dir isn't share").into_response();
+    };
+    let dir = share_path.path;
+    if !dir.is_dir() {
+        return (StatusCode::BAD_REQUEST, "Not a dir").into_response();
+    }
+
+    // HEAD 不算下载, 也不用打包
+    if method == Method::HEAD {
+        return [(header::CONTENT_TYPE, "application/zip")].into_response();
+    }
+    if !count_download(&state, &p.id, false).await {
+        return (StatusCode::GONE, "Share is used up").into_response();
+    }
+
+    let file_name = format!("{}.zip", file_name(&dir));
+    let response = (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, attachment(&file_name)),
+        ],
+        archive::zip_dir(dir),
+    )
+        .into_response();
+    track(&state, response, file_name, client.ip())
+}
+
+#[derive(Deserialize)]
+struct IdsParam {
+    #[serde(default)]
+    id: Vec<String>,
+}
+
+async fn download_tar(
+    MultiQuery(p): MultiQuery<IdsParam>,
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    method: Method,
+) -> impl IntoResponse {
+    tar_response(p, state, client.ip(), &method, false).await
+}
+
+async fn download_tar_gz(
+    MultiQuery(p): MultiQuery<IdsParam>,
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    method: Method,
+) -> impl IntoResponse {
+    tar_response(p, state, client.ip(), &method, true).await
+}
+
+/// 打包 id 指定的分享, 不指定则打包全部分享.
+/// 打包全部时跳过限制了下载次数的分享, 不会一下子把它们都用掉
+async fn tar_response(
+    p: IdsParam,
+    state: AppState,
+    client: IpAddr,
+    method: &Method,
+    gzip: bool,
+) -> Response {
+    let is_all = p.id.is_empty();
+    let id_arr = if is_all {
+        let share_arr = state.share_arr.read().await;
+        share_arr
+            .iter()
+            .filter(|s| s.max_downloads.is_none())
+            .map(|s| s.id.clone())
+            .collect()
+    } else {
+        p.id
+    };
+
+    // 先检查全部 id, 都有效才记下载次数
+    let mut roots = vec![];
+    let mut valid_id_arr = vec![];
+    for id in id_arr {
+        match resolve(&state.share_arr, &id, "").await {
+            Some(share_path) => {
+                roots.push(share_path.path);
+                valid_id_arr.push(id);
+            }
+            // 打包全部时跳过刚好失效的分享
+            None if is_all => {}
+            None => {
+                tracing::error!("Error tar, id isn't share");
+                return (StatusCode::NOT_FOUND, "Id isn't share").into_response();
+            }
+        }
+    }
+    let (content_type, file_name) = if gzip {
+        ("application/gzip", "kk.tar.gz")
+    } else {
+        ("application/x-tar", "kk.tar")
+    };
+    // HEAD 不算下载, 也不用打包
+    if *method == Method::HEAD {
+        return [(header::CONTENT_TYPE, content_type)].into_response();
+    }
+    if !count_downloads(&state, &valid_id_arr).await {
+        return (StatusCode::GONE, "Share is used up").into_response();
+    }
+    let response = (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, attachment(file_name)),
+        ],
+        archive::tar_paths(roots, gzip),
+    )
+        .into_response();
+    track(&state, response, file_name.to_string(), client)
+}
+
+/// 把下载交给 TUI 的 Transfers 显示, body 发出多少就记多少
+fn track(state: &AppState, mut response: Response, name: String, client: IpAddr) -> Response {
+    if !response.status().is_success() {
+        return response;
+    }
+    let total = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let transfer = Transfer::new(name.clone(), client, total);
+    let _ = state.event_tx.send(AppEvent::Transfer(transfer.clone()));
+
+    // body 由 access_log 统一计数, 这里只把 Transfer 带过去
+    response.extensions_mut().insert(transfer);
+    response.extensions_mut().insert(ShareName(name));
+    response
+}
+
+#[derive(Deserialize)]
+struct UploadParam {
+    name: Option<String>,
+}
+
+/// 上传到接收目录, 支持 multipart, 或者 body 即文件内容, 文件名由 name 参数指定
+async fn upload(
+    Query(p): Query<UploadParam>,
+    State(state): State<AppState>,
+    request: Request,
+) -> impl IntoResponse {
+    let Some(dir) = state.receive_info.read().await.dir.clone() else {
+        return (StatusCode::FORBIDDEN, "Upload is disabled").into_response();
+    };
+
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+
+    let mut name_arr = vec![];
+    let result = if is_multipart {
+        match Multipart::from_request(request, &state).await {
+            Ok(multipart) => save_multipart(&dir, multipart, &state, &mut name_arr).await,
+            Err(rejection) => return rejection.into_response(),
+        }
+    } else {
+        let Some(name) = p.name else {
+            return (StatusCode::BAD_REQUEST, "Missing name").into_response();
+        };
+        let stream = request.into_body().into_data_stream();
+        match receive::save_stream(&dir, &name, stream).await {
+            Ok(path) => {
+                name_arr.push(add_received(&state, path).await);
+                Ok(())
             }
+            Err(e) => Err(e),
+        }
+    };
+
+    match result {
+        Ok(()) => format!("上传成功: {}", name_arr.join(", ")).into_response(),
+        Err(e) => {
+            tracing::error!("Error upload file: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to upload file: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn save_multipart(
+    dir: &Path,
+    mut multipart: Multipart,
+    state: &AppState,
+    name_arr: &mut Vec<String>,
+) -> std::io::Result<()> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(std::io::Error::other)?
+    {
+        // 不是文件的字段跳过
+        let Some(name) = field.file_name().map(|n| n.to_string()) else {
+            continue;
+        };
+        let path = receive::save_stream(dir, &name, field).await?;
+        name_arr.push(add_received(state, path).await);
+    }
+    Ok(())
+}
+
+/// 记录接收完成的文件, 返回文件名
+async fn add_received(state: &AppState, path: PathBuf) -> String {
+    let name = file_name(&path);
+    state.receive_info.write().await.files.push(path);
+    let _ = state.event_tx.send(AppEvent::Changed);
+    name
+}
+
+/// 图片的缩略图, 不算下载次数, 也不显示在传输列表里
+async fn thumbnail(
+    UrlPath(p): UrlPath<ShareParam>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let Some(share_path) = resolve(&state.share_arr, &p.id, &p.path).await else {
+        return (StatusCode::NOT_FOUND, "File isn't share").into_response();
+    };
+    let path = share_path.path;
+    if !path.is_file() || !thumb::is_supported(&path) {
+        return (StatusCode::BAD_REQUEST, "Not an image").into_response();
+    }
+    let thumb_path = path.clone();
+    let result = tokio::task::spawn_blocking(move || thumb::thumbnail(&thumb_path))
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|result| result);
+    match result {
+        Ok(content) => (
+            [
+                (header::CONTENT_TYPE, "image/jpeg"),
+                (header::CACHE_CONTROL, "private, max-age=86400"),
+            ],
+            content,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error thumbnail {}: {e}", path.display());
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to make thumbnail",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Content-Disposition, 文件名可能是中文, 用 filename* 编码
+fn attachment(file_name: &str) -> String {
+    format!(
+        "attachment; filename*=UTF-8''{}",
+        utf8_percent_encode(file_name, NON_ALPHANUMERIC)
+    )
+}
+
+fn inline(file_name: &str) -> String {
+    format!(
+        "inline; filename*=UTF-8''{}",
+        utf8_percent_encode(file_name, NON_ALPHANUMERIC)
+    )
+}
+
+/// 文件的 ETag 和这次请求的区间
+struct FileRange {
+    len: u64,
+    etag: String,
+    last_modified: Option<String>,
+    range: RangeRequest,
+}
+
+async fn file_range(path: &Path, headers: &HeaderMap) -> Result<FileRange, std::io::Error> {
+    let metadata = tokio::fs::metadata(path).await?;
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = format!(
+        "\"{:x}-{:x}\"",
+        len,
+        modified
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    );
+    let last_modified = modified.map(httpdate::fmt_http_date);
+
+    // If-Range 对不上, 说明文件已经变了, 只能返回整个文件
+    let is_if_range_match = match headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+        Some(if_range) => if_range == etag || Some(if_range) == last_modified.as_deref(),
+        None => true,
+    };
+    let range = match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(range) if is_if_range_match => range::parse(range, len),
+        _ => RangeRequest::Full,
+    };
+    Ok(FileRange {
+        len,
+        etag,
+        last_modified,
+        range,
+    })
+}
+
+/// 发送文件, 支持 Range 断点续传和多区间请求
+async fn stream_file(
+    path: &Path,
+    file_range: FileRange,
+    content_type: &str,
+) -> Result<Response, std::io::Error> {
+    let FileRange {
+        len,
+        etag,
+        last_modified,
+        range,
+    } = file_range;
+
+    let mut builder = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag);
+    if let Some(last_modified) = &last_modified {
+        builder = builder.header(header::LAST_MODIFIED, last_modified);
+    }
+
+    let response = match range {
+        RangeRequest::Full => {
+            let file = File::open(path).await?;
+            let stream = ReaderStream::new(tokio::io::BufReader::new(file));
+            builder
+                .header(header::CONTENT_TYPE, content_type)
+                .header(header::CONTENT_LENGTH, len)
+                .body(Body::from_stream(stream))
+        }
+        RangeRequest::Partial(ranges) if ranges.len() == 1 => {
+            let (start, end) = ranges[0];
+            let stream = range::file_part(path, start, end).await?;
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_TYPE, content_type)
+                .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}"))
+                .header(header::CONTENT_LENGTH, end - start + 1)
+                .body(Body::from_stream(stream))
+        }
+        RangeRequest::Partial(ranges) => {
+            let boundary = format!(
+                "kk{:x}",
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos()
+            );
+            let (body, content_length) =
+                range::multipart_body(path.to_path_buf(), ranges, len, content_type, &boundary);
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    header::CONTENT_TYPE,
+                    format!("multipart/byteranges; boundary={boundary}"),
+                )
+                .header(header::CONTENT_LENGTH, content_length)
+                .body(body)
+        }
+        RangeRequest::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+            .body(Body::empty()),
+    };
+    response.map_err(std::io::Error::other)
+}
+
+/// 记一次下载, 分享已失效返回 false.
+/// 单个文件可以断点续传, 用完的分享等下载完成再移除, 打包下载则马上移除
+async fn count_download(state: &AppState, id: &str, is_resumable: bool) -> bool {
+    let mut share_arr = state.share_arr.write().await;
+    let is_taken = share::take_download(&mut share_arr, id);
+    if is_taken {
+        if !is_resumable {
+            share::remove_used_up(&mut share_arr, id);
+        }
+        // 刷新网页上的下载次数, 或者移除用完的分享
+        let _ = state.broadcast_tx.send(());
+    }
+    is_taken
+}
+
+/// 打包下载时一次记多个分享, 有一个已失效就都不记, 返回 false
+async fn count_downloads(state: &AppState, id_arr: &[String]) -> bool {
+    let mut share_arr = state.share_arr.write().await;
+    let is_taken = share::take_downloads(&mut share_arr, id_arr);
+    if is_taken && !id_arr.is_empty() {
+        for id in id_arr {
+            share::remove_used_up(&mut share_arr, id);
+        }
+        let _ = state.broadcast_tx.send(());
+    }
+    is_taken
+}
+
+/// 用完的分享在从头发到文件末尾的下载完成后移除, 中途断开的还能续传
+async fn remove_when_done(state: AppState, id: String, transfer: Arc<Transfer>) {
+    transfer.wait_finished().await;
+    if transfer.state() != TransferState::Done {
+        return;
+    }
+    if share::remove_used_up(&mut *state.share_arr.write().await, &id) {
+        state
+            .counted
+            .lock()
+            .unwrap()
+            .retain(|(counted_id, _, _)| *counted_id != id);
+        let _ = state.broadcast_tx.send(());
+    }
+}
+
+/// 每个区间都从中间开始, 是断点续传或者拖动进度条,
+/// 整个文件或者有区间从头开始都算新的下载
+fn is_resume(range: &RangeRequest) -> bool {
+    match range {
+        RangeRequest::Full => false,
+        RangeRequest::Partial(ranges) => ranges.iter().all(|(start, _)| *start > 0),
+        RangeRequest::Unsatisfiable => true,
+    }
+}
+
+/// 由分享 id 和分享内的相对路径找到实际路径, 不允许跳出分享目录
+async fn resolve(share_arr: &RwLock<Vec<Share>>, id: &str, rel: &str) -> Option<SharePath> {
+    resolve_share(share_arr, id, rel, false).await
+}
+
+/// is_used_up_ok 时次数已用完的分享也能找到, 给已经计过次数的断点续传用
+async fn resolve_share(
+    share_arr: &RwLock<Vec<Share>>,
+    id: &str,
+    rel: &str,
+    is_used_up_ok: bool,
+) -> Option<SharePath> {
+    let root = share_arr
+        .read()
+        .await
+        .iter()
+        .find(|s| s.id == id && !s.is_expired() && (is_used_up_ok || !s.is_used_up()))?
+        .path
+        .clone();
+    let root = tokio::fs::canonicalize(root).await.ok()?;
+
+    // 单个文件的分享, url 里的文件名只是给人看的
+    if !root.is_dir() {
+        return Some(SharePath {
+            id: id.to_string(),
+            path: root.clone(),
+            root,
+        });
+    }
+
+    let path = tokio::fs::canonicalize(root.join(rel.trim_start_matches('/')))
+        .await
+        .ok()?;
+    path.starts_with(&root).then(|| SharePath {
+        id: id.to_string(),
+        root,
+        path,
+    })
+}
+
+async fn share_arr_2_file_arr(share_arr: Arc<RwLock<Vec<Share>>>) -> Vec<FileInfo> {
+    let share_arr = share_arr.read().await;
+    share_arr
+        .iter()
+        .filter(|s| !s.is_used_up())
+        .map(|s| {
+            let mut file_info = FileInfo::new(&s.path, share_url(&s.id, &s.path, &s.path));
+            file_info.limit = limit_text(s);
+            file_info
         })
         .collect::<Vec<_>>()
 }
+
+fn limit_text(share: &Share) -> Option<String> {
+    let mut text = vec![];
+    if let Some(minutes) = share.minutes_left() {
+        text.push(format!("{minutes} 分钟后失效"));
+    }
+    if let Some(max) = share.max_downloads {
+        text.push(format!("已下载 {}/{max} 次", share.downloads));
+    }
+    (!text.is_empty()).then(|| text.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // share/ 下有 a.txt 和 sub/b.txt, 分享外面有 secret.txt
+    fn share_dir() -> (tempfile::TempDir, RwLock<Vec<Share>>, String) {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("share");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub").join("b.txt"), "b").unwrap();
+        fs::write(tmp.path().join("secret.txt"), "secret").unwrap();
+        let share = Share::new(root);
+        let id = share.id.clone();
+        (tmp, RwLock::new(vec![share]), id)
+    }
+
+    #[test]
+    fn safe_next_only_local_path() {
+        assert_eq!(safe_next("/browse/abc?x=1"), "/browse/abc?x=1");
+        for next in [
+            "",
+            "browse",
+            "https://evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "/\\/evil.com",
+            "/\t/evil.com",
+            "/\n",
+        ] {
+            assert_eq!(safe_next(next), "/", "{next:?}");
+        }
+    }
+
+    #[test]
+    fn resume_only_from_middle() {
+        assert!(!is_resume(&range::parse("bytes=0-", 100)));
+        assert!(!is_resume(&range::parse("bytes=00-", 100)));
+        assert!(!is_resume(&range::parse("bytes= 0-", 100)));
+        assert!(!is_resume(&range::parse("bytes=1-,0-0", 100)));
+        assert!(!is_resume(&range::parse("bytes=-100", 100)));
+        assert!(!is_resume(&range::parse("bytes=oops", 100)));
+        assert!(is_resume(&range::parse("bytes=50-", 100)));
+        assert!(is_resume(&range::parse("bytes=10-19,50-", 100)));
+        assert!(is_resume(&range::parse("bytes=200-", 100)));
+    }
+
+    #[tokio::test]
+    async fn resolve_inside_share() {
+        let (tmp, share_arr, id) = share_dir();
+        let root = tmp.path().join("share").canonicalize().unwrap();
+
+        let share_path = resolve(&share_arr, &id, "sub/b.txt").await.unwrap();
+        assert_eq!(share_path.path, root.join("sub").join("b.txt"));
+        assert_eq!(share_path.root, root);
+        assert_eq!(resolve(&share_arr, &id, "").await.unwrap().path, root);
+        assert_eq!(
+            resolve(&share_arr, &id, "sub/../a.txt").await.unwrap().path,
+            root.join("a.txt")
+        );
+        assert!(resolve(&share_arr, "nope", "a.txt").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_rejects_dot_dot() {
+        let (_tmp, share_arr, id) = share_dir();
+        for rel in [
+            "..",
+            "../secret.txt",
+            "sub/../../secret.txt",
+            "/../secret.txt",
+        ] {
+            assert!(resolve(&share_arr, &id, rel).await.is_none(), "{rel}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn resolve_rejects_symlink_escape() {
+        let (tmp, share_arr, id) = share_dir();
+        let root = tmp.path().join("share");
+        std::os::unix::fs::symlink(tmp.path().join("secret.txt"), root.join("link")).unwrap();
+        std::os::unix::fs::symlink(tmp.path(), root.join("up")).unwrap();
+
+        assert!(resolve(&share_arr, &id, "link").await.is_none());
+        assert!(resolve(&share_arr, &id, "up/secret.txt").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_file_share_ignores_path() {
+        let (tmp, _, _) = share_dir();
+        let file = tmp.path().join("secret.txt");
+        let share = Share::new(file.clone());
+        let id = share.id.clone();
+        let share_arr = RwLock::new(vec![share]);
+
+        let share_path = resolve(&share_arr, &id, "../share/a.txt").await.unwrap();
+        assert_eq!(share_path.path, file.canonicalize().unwrap());
+    }
+}
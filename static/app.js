@@ -0,0 +1,66 @@
+// 文件卡片在浏览器里排序, 目录总在前面
+// 首页的列表由 websocket 整个替换, 替换后按上次选的方式重新排
+let sortKey = localStorage.getItem("kk-sort-key") || "name";
+let isSortDesc = localStorage.getItem("kk-sort-desc") === "true";
+
+function cmpCard(a, b) {
+  const aDir = a.dataset.dir === "true";
+  const bDir = b.dataset.dir === "true";
+  if (aDir !== bDir) {
+    return aDir ? -1 : 1;
+  }
+  let order = sortKey === "name"
+    ? a.dataset.name.localeCompare(b.dataset.name, undefined, { numeric: true })
+    : Number(a.dataset[sortKey]) - Number(b.dataset[sortKey]);
+  return isSortDesc ? -order : order;
+}
+
+function sortCards() {
+  document.querySelectorAll("[data-sortable]").forEach((grid) => {
+    [...grid.children].sort(cmpCard).forEach((card) => grid.appendChild(card));
+  });
+  document.querySelectorAll("[data-sort]").forEach((button) => {
+    const isActive = button.dataset.sort === sortKey;
+    button.classList.toggle("font-bold", isActive);
+    button.textContent = button.dataset.label + (isActive ? (isSortDesc ? " ↓" : " ↑") : "");
+  });
+}
+
+// 再点一次同一个按钮换方向
+function setSort(key) {
+  isSortDesc = key === sortKey ? !isSortDesc : false;
+  sortKey = key;
+  localStorage.setItem("kk-sort-key", sortKey);
+  localStorage.setItem("kk-sort-desc", isSortDesc);
+  sortCards();
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  document.querySelectorAll("[data-sort]").forEach((button) => {
+    button.addEventListener("click", () => setSort(button.dataset.sort));
+  });
+  sortCards();
+  document.body.addEventListener("htmx:oobAfterSwap", sortCards);
+});
+
+// 预览内容换进弹窗后打开, 关闭时清空, 视频音频不再继续播放
+document.addEventListener("DOMContentLoaded", () => {
+  const dialog = document.getElementById("preview");
+  if (!dialog) {
+    return;
+  }
+  document.body.addEventListener("htmx:afterSwap", (event) => {
+    if (event.detail.target.id === "preview-body" && !dialog.open) {
+      dialog.showModal();
+    }
+  });
+  dialog.addEventListener("close", () => {
+    document.getElementById("preview-body").innerHTML = "";
+  });
+  // 点弹窗外面关闭
+  dialog.addEventListener("click", (event) => {
+    if (event.target === dialog) {
+      dialog.close();
+    }
+  });
+});
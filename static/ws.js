@@ -0,0 +1,61 @@
+// htmx 的 websocket 扩展, 只实现首页用到的部分: 连接 ws-connect 的地址,
+// 收到的 html 按 hx-swap-oob 换进页面, 断开后自动重连
+(() => {
+  // 重连间隔从 1 秒开始翻倍, 最多 30 秒
+  const MIN_RETRY_MS = 1000;
+  const MAX_RETRY_MS = 30000;
+
+  let api;
+
+  function wsUrl(path) {
+    const url = new URL(path, location.href);
+    url.protocol = url.protocol === "https:" ? "wss:" : "ws:";
+    return url.href;
+  }
+
+  function connect(elt, retryMs) {
+    const data = api.getInternalData(elt);
+    const socket = new WebSocket(wsUrl(elt.getAttribute("ws-connect")));
+    data.webSocket = socket;
+
+    socket.addEventListener("open", () => {
+      retryMs = MIN_RETRY_MS;
+    });
+    socket.addEventListener("message", (event) => {
+      const settleInfo = api.makeSettleInfo(elt);
+      const fragment = api.makeFragment(event.data);
+      for (const child of Array.from(fragment.children)) {
+        api.oobSwap(api.getAttributeValue(child, "hx-swap-oob") || "true", child, settleInfo);
+      }
+      api.settleImmediately(settleInfo.tasks);
+    });
+    socket.addEventListener("close", () => {
+      // 元素被移除时不再重连
+      if (data.webSocket !== socket || !document.body.contains(elt)) {
+        return;
+      }
+      setTimeout(() => connect(elt, Math.min(retryMs * 2, MAX_RETRY_MS)), retryMs);
+    });
+  }
+
+  htmx.defineExtension("ws", {
+    init(internalApi) {
+      api = internalApi;
+    },
+
+    onEvent(name, event) {
+      const elt = event.target;
+      if (!(elt instanceof Element) || !elt.hasAttribute("ws-connect")) {
+        return;
+      }
+      const data = api.getInternalData(elt);
+      if (name === "htmx:beforeProcessNode" && !data.webSocket) {
+        connect(elt, MIN_RETRY_MS);
+      } else if (name === "htmx:beforeCleanupElement" && data.webSocket) {
+        const socket = data.webSocket;
+        data.webSocket = null;
+        socket.close();
+      }
+    },
+  });
+})();
@@ -1,6 +1,6 @@
 // to active tailwind lsp, must have config file
 module.exports = {
-  content: ["./templates/**/*.{html}"],
+  content: ["./templates/**/*.html"],
   theme: {
     extend: {},
   },